@@ -18,10 +18,10 @@ source $HOME/.cargo/env
 cargo install wasm-pack
 
 # build the client project
-wasm-pack build client  --target web --release
+wasm-pack build . --target web --release
 
-# clean updater the client has build to save some space as heroku has a soft limit of 300MB
+# clean up after the client has been built to save some space as heroku has a soft limit of 300MB
 cargo clean
 
 # build the server project
-cargo build -p server  --release
+cargo build --release --bin server --features ssr
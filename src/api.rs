@@ -1,185 +1,729 @@
 use async_recursion::async_recursion;
 use crate::types::{
-    Comment, StoryItem, StoryPageData, StorySorting, UserData,
+    Comment, FailedFetch, Item, ItemKind, PollOption, StoryItem, StoryList,
+    StoryPageData, StorySorting, UserData,
 };
 use futures::future::join_all;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use scheduler::Scheduler;
+use std::sync::Arc;
 use thiserror::Error;
-
+pub use cache::Cached;
+#[cfg(feature = "caching")]
+pub use cache::Cache;
 #[cfg(feature = "caching")]
-use std::sync::Mutex;
-
-const BASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
-const TOP_STORIES: &str = "/topstories.json";
-const NEW_STORIES: &str = "/newstories.json";
-const BEST_STORIES: &str = "/beststories.json";
-const SHOW_STORIES: &str = "/showstories.json";
-const ASK_STORIES: &str = "/askstories.json";
-const JOB_STORIES: &str = "/jobstories.json";
-const ITEM_API: &str = "/item";
-const USER_API: &str = "/user";
-
-const STORIES_COUNT: usize = 20;
+use cache::Caches;
+pub use config::{
+    ApiConfig,
+    CacheConfig,
+};
+pub use retry::RetryPolicy;
+pub use offline::{
+    get_offline_story,
+    save_story_offline,
+};
+pub use search::{
+    get_search_results,
+    SearchHit,
+    SearchQuery,
+    SearchResults,
+};
+pub use updates::{
+    get_comment_update,
+    get_story_update,
+    get_updates,
+    Updates,
+};
+pub use snapshot::{
+    Manifest,
+    SnapshotTransport,
+    MANIFEST_FILE,
+    SNAPSHOT_VERSION,
+};
+pub use transport::{
+    FixtureTransport,
+    HnTransport,
+    ReqwestTransport,
+    TransportFuture,
+};
+
+mod cache;
+mod config;
+mod offline;
+mod retry;
+mod scheduler;
+mod search;
+mod snapshot;
+mod transport;
+mod updates;
+
+/// the number of stories in a page of a story listing
+pub const STORIES_COUNT: usize = 20;
+/// how many levels of replies are fetched along with a comment
 const COMMENT_DEPTH: i64 = 3;
+/// how many replies of a nested comment are fetched along with it
+const COMMENT_WIDTH: usize = 3;
+/// how many top-level comments, or replies when loading more of them, are fetched at a time
+pub const REPLIES_PAGE_SIZE: usize = 50;
+/// how many of the latest submissions of a user are fetched along with their profile
+pub const USER_SUBMISSIONS: usize = 30;
+
+/// A handle to the hackernews api.
+///
+/// All the requests are done through its transport, which is reqwest by default
+/// but can be swapped with a [`FixtureTransport`] to run without the network,
+/// or a [`SnapshotTransport`] when the config points to a snapshot.
+/// The clones of a client share its transport, its caches and the requests it has in flight.
+/// Only the [`ApiConfig`] is retained when a client is serialized.
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
+#[serde(from = "ApiConfig", into = "ApiConfig")]
+pub struct HnClient {
+    config: ApiConfig,
+    transport: Arc<dyn HnTransport>,
+    scheduler: Arc<Scheduler>,
+    #[cfg(feature = "caching")]
+    caches: Arc<Caches>,
+}
 
-#[cfg(feature = "caching")]
-lazy_static::lazy_static! {
-    static ref STORY_CACHE: Mutex<lru::LruCache<i64, StoryPageData>> =
-        Mutex::new(lru::LruCache::new(1000));
-    static ref STORY_PREVIEW_CACHE: Mutex<lru::LruCache<i64, StoryItem>> =
-        Mutex::new(lru::LruCache::new(1000));
+impl HnClient {
+    /// a client with the default config which does its requests through `transport`
+    pub fn new(transport: impl HnTransport + 'static) -> Self {
+        Self {
+            config: ApiConfig::default(),
+            transport: Arc::new(transport),
+            scheduler: Arc::new(Scheduler::default()),
+            #[cfg(feature = "caching")]
+            caches: Arc::new(Caches::default()),
+        }
+    }
+
+    /// use `config` for assembling the urls of the requests.
+    /// The responses cached from the previous api are dropped
+    pub fn with_config(mut self, config: ApiConfig) -> Self {
+        self.config = config;
+        #[cfg(feature = "caching")]
+        {
+            self.caches = Arc::new(Caches::default());
+        }
+        self
+    }
+
+    pub fn config(&self) -> &ApiConfig {
+        &self.config
+    }
 }
 
-pub async fn get_stories() -> Result<Vec<StoryItem>, ServerError>{
-    get_stories_with_sorting(StorySorting::default()).await
+impl Default for HnClient {
+    fn default() -> Self {
+        Self::from(ApiConfig::default())
+    }
 }
 
+impl From<ApiConfig> for HnClient {
+    fn from(config: ApiConfig) -> Self {
+        match &config.snapshot {
+            Some(location) => {
+                let transport =
+                    SnapshotTransport::new(location.clone(), config.base_url.clone());
+                Self::new(transport).with_config(config)
+            }
+            // the timeout is applied to every request, whatever the transport
+            None => Self::new(ReqwestTransport::default()).with_config(config),
+        }
+    }
+}
+
+impl From<HnClient> for ApiConfig {
+    fn from(client: HnClient) -> Self {
+        client.config
+    }
+}
+
+impl std::fmt::Debug for HnClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("HnClient").finish_non_exhaustive()
+    }
+}
+
+pub async fn get_stories(
+    client: &HnClient,
+) -> Result<Fetched<StoryItem>, ServerError> {
+    get_stories_with_sorting(client, StorySorting::default()).await
+}
+
+/// the stories of the first page of the `sort` listing, along with the ones which failed
 pub async fn get_stories_with_sorting(
+    client: &HnClient,
     sort: StorySorting,
-) -> Result<Vec<StoryItem>, ServerError> {
-    let stories_api = match sort {
-        StorySorting::Best => BEST_STORIES,
-        StorySorting::Top => TOP_STORIES,
-        StorySorting::New => NEW_STORIES,
-        StorySorting::Show => SHOW_STORIES,
-        StorySorting::Ask => ASK_STORIES,
-        StorySorting::Job => JOB_STORIES,
-    };
+) -> Result<Fetched<StoryItem>, ServerError> {
+    let story_list = get_stories_page(client, sort, 1, STORIES_COUNT).await?;
+    Ok(Fetched {
+        items: story_list.stories,
+        failed: story_list.failed,
+    })
+}
 
-    let url = format!("{}{}", BASE_URL, stories_api);
-    let story_ids = make_json_get_request::<Vec<i64>>(&url).await?;
-    println!("story_ids:({}) {:#?}", story_ids.len(), story_ids);
-    let first_story_ids = &story_ids[..story_ids.len().min(STORIES_COUNT)];
-    let story_futures = first_story_ids
-        .iter()
-        .map(|story_id| get_story_preview(*story_id));
+/// fetch the story ids of the `sort` listing and the stories of `page`,
+/// where the first page is 1
+pub async fn get_stories_page(
+    client: &HnClient,
+    sort: StorySorting,
+    page: usize,
+    page_size: usize,
+) -> Result<StoryList, ServerError> {
+    let story_ids = get_story_ids(client, sort).await?;
+    log::trace!("story_ids:({}) {:?}", story_ids.len(), story_ids);
+    get_stories_page_with_ids(client, sort, story_ids, page, page_size).await
+}
 
-    let mut stories = join_all(story_futures)
-        .await
-        .into_iter()
-        .filter_map(|c| c.ok())
-        .collect::<Vec<_>>();
+/// fetch the story ids of the `sort` listing, in their ranking order
+pub async fn get_story_ids(
+    client: &HnClient,
+    sort: StorySorting,
+) -> Result<Vec<i64>, ServerError> {
+    #[cfg(feature = "caching")]
+    if let Some(story_ids) =
+        client.caches.lists.get_fresh(&sort, client.config.cache.lists_ttl_secs)
+    {
+        return Ok(story_ids);
+    }
 
-    stories.sort_unstable_by(|a,b|a.id.cmp(&b.id));
+    let url = client.config.stories_url(sort);
+    let story_ids = make_json_get_request::<Vec<i64>>(client, &url).await?;
 
-    Ok(stories)
+    #[cfg(feature = "caching")]
+    client.caches.lists.put(sort, story_ids.clone());
+
+    Ok(story_ids)
 }
 
-pub async fn get_story(story_id: i64) -> Result<StoryPageData, ServerError> {
+/// the cached page of the `sort` listing, even if it is stale, when the listing
+/// and every story of the page are cached
+pub fn get_cached_stories_page(
+    client: &HnClient,
+    sort: StorySorting,
+    page: usize,
+    page_size: usize,
+) -> Option<Cached<StoryList>> {
     #[cfg(feature = "caching")]
-    if let Some(cached_story) = STORY_CACHE.lock().unwrap().get(&story_id) {
-        return Ok(cached_story.clone());
+    {
+        let ttl = &client.config.cache;
+        let story_ids = client.caches.lists.get(&sort, ttl.lists_ttl_secs)?;
+        let mut story_list =
+            StoryList::new(sort, story_ids.value, page, page_size);
+        let stories = story_list
+            .page_ids()
+            .iter()
+            .map(|story_id| {
+                client.caches.story_previews.get(story_id, ttl.previews_ttl_secs)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let is_stale =
+            story_ids.is_stale || stories.iter().any(|story| story.is_stale);
+        let fetched_at = stories
+            .iter()
+            .map(|story| story.fetched_at)
+            .fold(story_ids.fetched_at, std::cmp::min);
+        story_list.stories =
+            stories.into_iter().map(|story| story.value).collect();
+        Some(Cached {
+            value: story_list,
+            fetched_at,
+            is_stale,
+        })
+    }
+    #[cfg(not(feature = "caching"))]
+    {
+        let _ = (client, sort, page, page_size);
+        None
     }
+}
 
-    let url = format!("{}{}/{}.json", BASE_URL, ITEM_API, story_id);
-    let mut story = make_json_get_request::<StoryPageData>(&url).await?;
-    let comment_ids = &story.kids[..story.kids.len().min(50)];  // Show up to 50 top-level comments
-    let comments = join_all(
-        comment_ids
-            .iter()
-            .map(|story_id| get_comment_with_depth(*story_id, COMMENT_DEPTH)),
-    )
-    .await
-    .into_iter()
-    .filter_map(|c| c.ok())
-    .collect();
+/// fetch the stories of `page` from an already fetched list of story ids
+pub async fn get_stories_page_with_ids(
+    client: &HnClient,
+    sort: StorySorting,
+    story_ids: Vec<i64>,
+    page: usize,
+    page_size: usize,
+) -> Result<StoryList, ServerError> {
+    let mut story_list = StoryList::new(sort, story_ids, page, page_size);
+    let page_ids = story_list.page_ids();
+    let story_futures = page_ids
+        .iter()
+        .map(|story_id| get_story_preview(client, *story_id));
+
+    // The previews are fetched concurrently, but join_all yields the results
+    // in the same order as the futures, so pairing them with the page ids
+    // keeps the ranking of the listing.
+    let fetched = Fetched::from_results(page_ids, join_all(story_futures).await);
 
-    story.comments = comments;
+    story_list.stories = fetched.items;
+    story_list.failed = fetched.failed;
+    Ok(story_list)
+}
+
+pub async fn get_story(
+    client: &HnClient,
+    story_id: i64,
+) -> Result<StoryPageData, ServerError> {
+    #[cfg(feature = "caching")]
+    if let Some(cached_story) =
+        client.caches.stories.get_fresh(&story_id, client.config.cache.stories_ttl_secs)
+    {
+        return Ok(cached_story);
+    }
 
+    let item = get_item(client, story_id).await?;
+    let kind = item.kind();
+    let mut story = item
+        .into_story_page()
+        .ok_or(ServerError::UnexpectedItem { id: story_id, kind })?;
+    let comment_ids = &story.kids[..story.kids.len().min(REPLIES_PAGE_SIZE)];
+    let comments = get_comments(client, comment_ids).await;
+    let poll_options = get_poll_options(client, &story.parts).await;
+
+    story.comments = comments.items;
+    story.poll_options = poll_options.items;
+    story.failed = comments.failed;
+    story.failed.extend(poll_options.failed);
+
+    // an incomplete story is fetched whole again the next time, rather than cached
     #[cfg(feature = "caching")]
-    STORY_CACHE.lock().unwrap().put(story_id, story.clone());
+    if story.failed.is_empty() {
+        client.caches.stories.put(story_id, story.clone());
+    }
 
     Ok(story)
 }
 
+/// the cached story, even if it is stale, so it can be displayed while it is refetched
+pub fn get_cached_story(
+    client: &HnClient,
+    story_id: i64,
+) -> Option<Cached<StoryPageData>> {
+    #[cfg(feature = "caching")]
+    return client.caches.stories.get(&story_id, client.config.cache.stories_ttl_secs);
+    #[cfg(not(feature = "caching"))]
+    {
+        let _ = (client, story_id);
+        None
+    }
+}
+
+/// drop the cached copies of the story, so it is fetched again the next time
+pub fn invalidate_story(client: &HnClient, story_id: i64) {
+    #[cfg(feature = "caching")]
+    {
+        client.caches.stories.remove(&story_id);
+        client.caches.story_previews.remove(&story_id);
+    }
+    #[cfg(not(feature = "caching"))]
+    let _ = (client, story_id);
+}
+
+/// drop the cached story ids of the listing
+pub fn invalidate_stories(client: &HnClient, sort: StorySorting) {
+    #[cfg(feature = "caching")]
+    client.caches.lists.remove(&sort);
+    #[cfg(not(feature = "caching"))]
+    let _ = (client, sort);
+}
+
+pub fn invalidate_comment(client: &HnClient, comment_id: i64) {
+    #[cfg(feature = "caching")]
+    client.caches.comments.remove(&comment_id);
+    #[cfg(not(feature = "caching"))]
+    let _ = (client, comment_id);
+}
+
+pub fn invalidate_user(client: &HnClient, user_id: &str) {
+    #[cfg(feature = "caching")]
+    client.caches.users.remove(&user_id.to_string());
+    #[cfg(not(feature = "caching"))]
+    let _ = (client, user_id);
+}
+
+/// drop everything that is cached
+pub fn clear_caches(client: &HnClient) {
+    #[cfg(feature = "caching")]
+    {
+        client.caches.stories.clear();
+        client.caches.story_previews.clear();
+        client.caches.lists.clear();
+        client.caches.comments.clear();
+        client.caches.users.clear();
+    }
+    #[cfg(not(feature = "caching"))]
+    let _ = client;
+}
+
+/// fetch the options of a poll
+pub async fn get_poll_options(
+    client: &HnClient,
+    option_ids: &[i64],
+) -> Fetched<PollOption> {
+    let results = join_all(option_ids.iter().map(|option_id| async move {
+        let item = get_item(client, *option_id).await?;
+        let kind = item.kind();
+        item.into_poll_option().ok_or(ServerError::UnexpectedItem {
+            id: *option_id,
+            kind,
+        })
+    }))
+    .await;
+    Fetched::from_results(option_ids, results)
+}
+
 // Same as get_story but does not add comments
-pub async fn get_story_preview(story_id: i64) -> Result<StoryItem, ServerError> {
+pub async fn get_story_preview(
+    client: &HnClient,
+    story_id: i64,
+) -> Result<StoryItem, ServerError> {
     #[cfg(feature = "caching")]
-    if let Some(cached_story) =
-        STORY_PREVIEW_CACHE.lock().unwrap().get(&story_id)
+    if let Some(cached_story) = client.caches.story_previews
+        .get_fresh(&story_id, client.config.cache.previews_ttl_secs)
     {
-        return Ok(cached_story.clone());
+        return Ok(cached_story);
     }
 
-    let url = format!("{}{}/{}.json", BASE_URL, ITEM_API, story_id);
-    let story_preview = make_json_get_request::<StoryItem>(&url).await?;
+    let item = get_item(client, story_id).await?;
+    let kind = item.kind();
+    let story_preview = item
+        .into_story()
+        .ok_or(ServerError::UnexpectedItem { id: story_id, kind })?;
 
     #[cfg(feature = "caching")]
-    STORY_PREVIEW_CACHE
-        .lock()
-        .unwrap()
-        .put(story_id, story_preview.clone());
+    client.caches.story_previews.put(story_id, story_preview.clone());
 
     Ok(story_preview)
 }
 
+/// fetch the items of `item_ids` at the same time, whatever their kind
+pub async fn get_items(client: &HnClient, item_ids: &[i64]) -> Fetched<Item> {
+    let results =
+        join_all(item_ids.iter().map(|item_id| get_item(client, *item_id)))
+            .await;
+    Fetched::from_results(item_ids, results)
+}
 
+/// fetch any kind of item, the other item functions are built on top of this
+pub async fn get_item(
+    client: &HnClient,
+    item_id: i64,
+) -> Result<Item, ServerError> {
+    let url = client.config.item_url(item_id);
+    make_json_get_request::<Option<Item>>(client, &url)
+        .await?
+        .ok_or(ServerError::NotFound {
+            kind: Resource::Item,
+            id: item_id.to_string(),
+        })
+}
 
-#[cfg_attr(target_arch = "wasm32", async_recursion(?Send))]
-#[cfg_attr(not(target_arch = "wasm32"), async_recursion)]
+/// fetch the comment with `depth` levels of its replies, the
+/// replies which failed to load are left out of the comment tree
 pub async fn get_comment_with_depth(
-    story_id: i64,
+    client: &HnClient,
+    comment_id: i64,
     depth: i64,
 ) -> Result<Comment, ServerError> {
-    let url = format!("{}{}/{}.json", BASE_URL, ITEM_API, story_id);
-    let mut comment = make_json_get_request::<Comment>(&url).await?;
+    let (comment, _failed) = get_comment_tree(client, comment_id, depth).await?;
+    Ok(comment)
+}
+
+/// fetch the comment with `depth` levels of its replies, along with the replies at any
+/// level which failed to load. They are left out of the tree, so they are displayed as
+/// replies which are not loaded yet until they are fetched again.
+#[cfg_attr(target_arch = "wasm32", async_recursion(?Send))]
+#[cfg_attr(not(target_arch = "wasm32"), async_recursion)]
+async fn get_comment_tree(
+    client: &HnClient,
+    comment_id: i64,
+    depth: i64,
+) -> Result<(Comment, Vec<FailedFetch>), ServerError> {
+    let mut comment = get_bare_comment(client, comment_id).await?;
+    let mut failed = vec![];
     if depth > 0 {
-        let sub_comment_ids = &comment.kids[..comment.kids.len().min(3)];
-        let sub_comments = join_all(
+        let sub_comment_ids = &comment.kids[..comment.kids.len().min(COMMENT_WIDTH)];
+        let results = join_all(
             sub_comment_ids
                 .iter()
-                .map(|story_id| get_comment_with_depth(*story_id, depth - 1)),
+                .map(|sub_comment_id| {
+                    get_comment_tree(client, *sub_comment_id, depth - 1)
+                }),
         )
-        .await
-        .into_iter()
-        .filter_map(|c| c.ok())
-        .collect();
+        .await;
+        let fetched = Fetched::from_results(sub_comment_ids, results);
+        failed = fetched.failed;
+        for (sub_comment, sub_failed) in fetched.items {
+            comment.sub_comments.push(sub_comment);
+            failed.extend(sub_failed);
+        }
+    }
+    Ok((comment, failed))
+}
 
-        comment.sub_comments = sub_comments;
+/// fetch the comment without any of its replies
+async fn get_bare_comment(
+    client: &HnClient,
+    comment_id: i64,
+) -> Result<Comment, ServerError> {
+    #[cfg(feature = "caching")]
+    if let Some(comment) =
+        client.caches.comments.get_fresh(&comment_id, client.config.cache.comments_ttl_secs)
+    {
+        return Ok(comment);
     }
+
+    let item = get_item(client, comment_id).await?;
+    let kind = item.kind();
+    let comment = item
+        .into_comment()
+        .ok_or(ServerError::UnexpectedItem { id: comment_id, kind })?;
+
+    #[cfg(feature = "caching")]
+    client.caches.comments.put(comment_id, comment.clone());
+
     Ok(comment)
 }
 
-pub async fn get_comment(comment_id: i64) -> Result<Comment, ServerError> {
-    let comment = get_comment_with_depth(comment_id, COMMENT_DEPTH).await?;
+/// fetch the comments of `comment_ids` together with their first few levels of replies,
+/// this is used for loading the replies that were left out of the comment tree
+pub async fn get_comments(
+    client: &HnClient,
+    comment_ids: &[i64],
+) -> Fetched<Comment> {
+    let results = join_all(
+        comment_ids
+            .iter()
+            .map(|comment_id| get_comment_tree(client, *comment_id, COMMENT_DEPTH)),
+    )
+    .await;
+    let fetched = Fetched::from_results(comment_ids, results);
+    let mut comments = Fetched {
+        items: vec![],
+        failed: fetched.failed,
+    };
+    for (comment, failed) in fetched.items {
+        comments.items.push(comment);
+        comments.failed.extend(failed);
+    }
+    comments
+}
+
+/// fetch the comment of a permalink with its first few levels of replies,
+/// the replies which failed to load are displayed as not loaded yet
+pub async fn get_comment(
+    client: &HnClient,
+    comment_id: i64,
+) -> Result<Comment, ServerError> {
+    let comment =
+        get_comment_with_depth(client, comment_id, COMMENT_DEPTH).await?;
     Ok(comment)
 }
 
 
-pub async fn get_user_page(user_id: &str) -> Result<UserData, ServerError> {
-    let url = format!("{}{}/{}.json", BASE_URL, USER_API, user_id);
-    let mut user = make_json_get_request::<UserData>(&url).await?;
+pub async fn get_user_page(
+    client: &HnClient,
+    user_id: &str,
+) -> Result<UserData, ServerError> {
+    #[cfg(feature = "caching")]
+    if let Some(user) = client.caches.users
+        .get_fresh(&user_id.to_string(), client.config.cache.users_ttl_secs)
+    {
+        return Ok(user);
+    }
+
+    let url = client.config.user_url(user_id);
+    let mut user = make_json_get_request::<Option<UserData>>(client, &url)
+        .await?
+        .ok_or_else(|| ServerError::NotFound {
+            kind: Resource::User,
+            id: user_id.to_string(),
+        })?;
     //submitted could be comments or story post
-    let first_story_ids = &user.submitted[..user.submitted.len().min(30)];
-    let story_futures = first_story_ids
-        .iter()
-        .map(|story_id| get_story_preview(*story_id));
+    let first_item_ids = &user.submitted[..user.submitted.len().min(USER_SUBMISSIONS)];
+    let items = get_items(client, first_item_ids).await;
+
+    for item in items.items {
+        if item.is_story() {
+            user.stories.extend(item.into_story());
+        } else {
+            user.comments.extend(item.into_comment());
+        }
+    }
+    user.failed = items.failed;
 
-    // we only filter the success where other types such as comments fail
-    let stories = join_all(story_futures)
-        .await
-        .into_iter()
-        .filter_map(|story| story.ok())
-        .collect();
+    log::trace!("user: {:?}", user);
 
-    user.stories = stories;
+    // an incomplete user page is fetched whole again the next time, rather than cached
+    #[cfg(feature = "caching")]
+    if user.failed.is_empty() {
+        client.caches.users.put(user_id.to_string(), user.clone());
+    }
 
-    dbg!(&user);
     Ok(user)
 }
 
-#[derive(Error, Debug)]
+/// the cached user page, even if it is stale, so it can be displayed while it is refetched
+pub fn get_cached_user_page(
+    client: &HnClient,
+    user_id: &str,
+) -> Option<Cached<UserData>> {
+    #[cfg(feature = "caching")]
+    return client.caches.users.get(&user_id.to_string(), client.config.cache.users_ttl_secs);
+    #[cfg(not(feature = "caching"))]
+    {
+        let _ = (client, user_id);
+        None
+    }
+}
+
+/// The items of a batch which could be fetched, along with the ones which failed
+#[derive(Clone, Debug, PartialEq)]
+pub struct Fetched<T> {
+    pub items: Vec<T>,
+    pub failed: Vec<FailedFetch>,
+}
+
+impl<T> Fetched<T> {
+    /// pair the `results` with the `ids` they were fetched for, in the same order
+    fn from_results(ids: &[i64], results: Vec<Result<T, ServerError>>) -> Self {
+        let mut fetched = Fetched {
+            items: vec![],
+            failed: vec![],
+        };
+        for (id, result) in ids.iter().zip(results) {
+            match result {
+                Ok(item) => fetched.items.push(item),
+                Err(error) => {
+                    log::warn!("unable to fetch item {}: {}", id, error);
+                    fetched.failed.push(FailedFetch { id: *id, error });
+                }
+            }
+        }
+        fetched
+    }
+}
+
+/// What a request was looking for
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Resource {
+    Item,
+    User,
+}
+
+impl std::fmt::Display for Resource {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Resource::Item => write!(f, "item"),
+            Resource::User => write!(f, "user"),
+        }
+    }
+}
+
+/// The errors of the api.
+///
+/// The errors of the underlying libraries are kept as their message,
+/// so the errors can be cloned and serialized along with the app state.
+#[derive(Error, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ServerError {
-    #[error("reqwest error: {0}")]
-    Reqwest(#[from] reqwest::Error),
+    /// the request could not be done, `retryable` is set when it may succeed on another try
+    #[error("reqwest error: {message}")]
+    Reqwest { message: String, retryable: bool },
     #[error("json error: {0}")]
-    SerdeJson(#[from] serde_json::Error),
+    SerdeJson(String),
+    #[error("io error: {0}")]
+    Io(String),
+    #[error("storage error: {0}")]
+    Storage(String),
+    #[error("the server responded with status {0}")]
+    HttpStatus(u16),
+    #[error("the request timed out after {0}ms")]
+    Timeout(u64),
+    /// the api responded with `null`, which it does for the items and users that don't exist
+    #[error("there is no {kind} {id}")]
+    NotFound { kind: Resource, id: String },
+    #[error("there is no network connection")]
+    Offline,
+    /// a snapshot only holds the urls of the api it is taken from, and not the search
+    #[error("{0} is not in the snapshot")]
+    NotInSnapshot(String),
+    /// the snapshot is incomplete, or of a layout this app does not read
+    #[error("invalid snapshot: {0}")]
+    InvalidSnapshot(String),
+    #[error("item {id} is a {kind:?}, which is not expected here")]
+    UnexpectedItem { id: i64, kind: ItemKind },
+}
+
+impl From<reqwest::Error> for ServerError {
+    fn from(e: reqwest::Error) -> Self {
+        ServerError::Reqwest {
+            message: e.to_string(),
+            // an invalid url or an undecodable body are the same however many times they are tried
+            retryable: !e.is_builder() && !e.is_decode(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ServerError {
+    fn from(e: serde_json::Error) -> Self {
+        ServerError::SerdeJson(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for ServerError {
+    fn from(e: std::io::Error) -> Self {
+        ServerError::Io(e.to_string())
+    }
 }
 
 
 pub async fn make_json_get_request<T: serde::de::DeserializeOwned>(
+    client: &HnClient,
     url: &str,
 ) -> Result<T, ServerError> {
-    dbg!(url);
-    let response = reqwest::get(url).await?;
-    Ok(response.json::<T>().await?)
+    log::trace!("fetching {}", url);
+    let body = client
+        .scheduler
+        .dedup(url, || {
+            let client = client.clone();
+            let url = url.to_string();
+            Box::pin(async move { get_body(&client, &url).await })
+        })
+        .await?;
+    Ok(serde_json::from_str::<T>(&body)?)
+}
+
+/// fetch the body of `url`, retrying the transient failures.
+/// Only the attempts take up a slot of the scheduler, not the backoffs in between.
+async fn get_body(client: &HnClient, url: &str) -> Result<String, ServerError> {
+    let retry = &client.config.retry;
+    let mut attempt = 1;
+    loop {
+        let permit = client.scheduler.acquire(client.config.max_in_flight).await;
+        let request = client.transport.get(url);
+        let result = retry::with_timeout(client.config.timeout_ms, request).await;
+        drop(permit);
+        match result {
+            Ok(body) => return Ok(body),
+            Err(e) if e.is_retryable() && attempt < retry.max_attempts => {
+                let backoff_ms = retry.backoff_ms(attempt);
+                log::warn!(
+                    "attempt {} of {} failed: {}, retrying in {}ms",
+                    attempt,
+                    url,
+                    e,
+                    backoff_ms
+                );
+                retry::sleep(backoff_ms).await;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
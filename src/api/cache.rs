@@ -0,0 +1,111 @@
+//! In-memory caches of the api responses, where every entry expires after a ttl.
+//!
+//! An expired entry is not dropped right away, it is still handed out as stale
+//! so the content can be displayed immediately while a fresh copy is fetched.
+use chrono::{
+    DateTime,
+    Utc,
+};
+#[cfg(feature = "caching")]
+use crate::types::{
+    Comment,
+    StoryItem,
+    StoryPageData,
+    StorySorting,
+    UserData,
+};
+#[cfg(feature = "caching")]
+use std::{
+    hash::Hash,
+    sync::Mutex,
+};
+
+/// A cached value, along with whether it has outlived its ttl
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cached<V> {
+    pub value: V,
+    pub fetched_at: DateTime<Utc>,
+    pub is_stale: bool,
+}
+
+impl<V> Cached<V> {
+    pub fn map<U>(self, f: impl FnOnce(V) -> U) -> Cached<U> {
+        Cached {
+            value: f(self.value),
+            fetched_at: self.fetched_at,
+            is_stale: self.is_stale,
+        }
+    }
+}
+
+/// The caches of a client, one for each kind of response. They are shared by the clones
+/// of the client, while the clients made separately, which may read other apis, have their own
+#[cfg(feature = "caching")]
+pub(crate) struct Caches {
+    pub stories: Cache<i64, StoryPageData>,
+    pub story_previews: Cache<i64, StoryItem>,
+    pub lists: Cache<StorySorting, Vec<i64>>,
+    /// the comments without their replies, the comment trees are assembled from these
+    pub comments: Cache<i64, Comment>,
+    pub users: Cache<String, UserData>,
+}
+
+#[cfg(feature = "caching")]
+impl Default for Caches {
+    fn default() -> Self {
+        Self {
+            stories: Cache::new(1000),
+            story_previews: Cache::new(1000),
+            lists: Cache::new(10),
+            comments: Cache::new(5000),
+            users: Cache::new(100),
+        }
+    }
+}
+
+/// A least recently used cache which keeps the time each entry was put in
+#[cfg(feature = "caching")]
+pub struct Cache<K: Hash + Eq, V> {
+    entries: Mutex<lru::LruCache<K, (V, DateTime<Utc>)>>,
+}
+
+#[cfg(feature = "caching")]
+impl<K: Hash + Eq, V: Clone> Cache<K, V> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: Mutex::new(lru::LruCache::new(capacity)),
+        }
+    }
+
+    /// the entry of `key`, which is stale when it is older than `ttl_secs`
+    pub fn get(&self, key: &K, ttl_secs: u64) -> Option<Cached<V>> {
+        let mut entries = self.entries.lock().expect("cache lock poisoned");
+        entries.get(key).map(|(value, fetched_at)| Cached {
+            value: value.clone(),
+            fetched_at: *fetched_at,
+            is_stale: (Utc::now() - *fetched_at).num_seconds() >= ttl_secs as i64,
+        })
+    }
+
+    /// the entry of `key`, only if it is not older than `ttl_secs`
+    pub fn get_fresh(&self, key: &K, ttl_secs: u64) -> Option<V> {
+        self.get(key, ttl_secs)
+            .filter(|cached| !cached.is_stale)
+            .map(|cached| cached.value)
+    }
+
+    pub fn put(&self, key: K, value: V) {
+        self.entries
+            .lock()
+            .expect("cache lock poisoned")
+            .put(key, (value, Utc::now()));
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().expect("cache lock poisoned").pop(key);
+    }
+
+    pub fn clear(&self) {
+        self.entries.lock().expect("cache lock poisoned").clear();
+    }
+}
@@ -0,0 +1,186 @@
+//! Runtime configuration of the endpoints the api talks to.
+use super::RetryPolicy;
+use crate::types::StorySorting;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+const BASE_URL: &str = "https://hacker-news.firebaseio.com/v0";
+const TOP_STORIES: &str = "/topstories.json";
+const NEW_STORIES: &str = "/newstories.json";
+const BEST_STORIES: &str = "/beststories.json";
+const SHOW_STORIES: &str = "/showstories.json";
+const ASK_STORIES: &str = "/askstories.json";
+const JOB_STORIES: &str = "/jobstories.json";
+const ITEM_API: &str = "/item";
+const USER_API: &str = "/user";
+const UPDATES_API: &str = "/updates.json";
+/// poll for updates every 30 seconds, which is about how often the api refreshes them
+const UPDATES_INTERVAL_MS: u64 = 30_000;
+const SEARCH_URL: &str = "https://hn.algolia.com/api/v1";
+const TIMEOUT_MS: u64 = 10_000;
+/// browsers open at most 6 connections per host over http/1, but the api is served over http/2
+const MAX_IN_FLIGHT: usize = 16;
+const OFFLINE_STORE: &str = "hackernews-offline";
+/// the scores and comment counts of the listings change often
+const PREVIEWS_TTL_SECS: u64 = 5 * 60;
+const STORIES_TTL_SECS: u64 = 60;
+const LISTS_TTL_SECS: u64 = 60;
+const COMMENTS_TTL_SECS: u64 = 5 * 60;
+const USERS_TTL_SECS: u64 = 10 * 60;
+
+/// the name of the `<meta>` tag the config can be supplied with
+#[cfg(feature = "wasm")]
+const META_NAME: &str = "hn-api-config";
+
+/// Where the firebase compatible api is located.
+///
+/// Every field is optional when deserializing, so a mirror that only differs
+/// in its base url can be configured with `{"base_url": "https://hn.example.com/v0"}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiConfig {
+    /// the url where all the endpoint paths are appended to
+    pub base_url: String,
+    pub top_stories: String,
+    pub new_stories: String,
+    pub best_stories: String,
+    pub show_stories: String,
+    pub ask_stories: String,
+    pub job_stories: String,
+    /// the path of items, the item id is appended to this
+    pub item_api: String,
+    /// the path of users, the username is appended to this
+    pub user_api: String,
+    /// the path of the recently changed items and profiles
+    pub updates_api: String,
+    /// how often the displayed content is checked for updates, `None` disables the live updates
+    pub updates_interval_ms: Option<u64>,
+    /// the url of the algolia compatible search api
+    pub search_url: String,
+    /// how long a request is waited for before it is given up on, or retried
+    pub timeout_ms: Option<u64>,
+    /// how the requests that failed for a transient reason are retried
+    pub retry: RetryPolicy,
+    /// how many requests are done at the same time, the others wait for their turn
+    pub max_in_flight: usize,
+    /// read everything from the static snapshot at this location instead of the api,
+    /// which is a directory on native targets and a url, possibly relative, in the browser
+    pub snapshot: Option<String>,
+    /// where the stories saved for offline reading are kept, which is
+    /// the IndexedDB database name in the browser and a directory on native targets
+    pub offline_store: String,
+    /// how long the cached responses are fresh
+    pub cache: CacheConfig,
+}
+
+/// How long the cached responses are fresh, in seconds.
+///
+/// Stale responses are still displayed right away, but they are refetched
+/// in the background instead of being used as is.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// the story ids of the listings
+    pub lists_ttl_secs: u64,
+    /// the story previews of the listings
+    pub previews_ttl_secs: u64,
+    /// the stories with their comments
+    pub stories_ttl_secs: u64,
+    /// the individual comments, without their replies
+    pub comments_ttl_secs: u64,
+    /// the user pages, along with their submissions
+    pub users_ttl_secs: u64,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            lists_ttl_secs: LISTS_TTL_SECS,
+            previews_ttl_secs: PREVIEWS_TTL_SECS,
+            stories_ttl_secs: STORIES_TTL_SECS,
+            comments_ttl_secs: COMMENTS_TTL_SECS,
+            users_ttl_secs: USERS_TTL_SECS,
+        }
+    }
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: BASE_URL.to_string(),
+            top_stories: TOP_STORIES.to_string(),
+            new_stories: NEW_STORIES.to_string(),
+            best_stories: BEST_STORIES.to_string(),
+            show_stories: SHOW_STORIES.to_string(),
+            ask_stories: ASK_STORIES.to_string(),
+            job_stories: JOB_STORIES.to_string(),
+            item_api: ITEM_API.to_string(),
+            user_api: USER_API.to_string(),
+            updates_api: UPDATES_API.to_string(),
+            updates_interval_ms: Some(UPDATES_INTERVAL_MS),
+            search_url: SEARCH_URL.to_string(),
+            timeout_ms: Some(TIMEOUT_MS),
+            retry: RetryPolicy::default(),
+            max_in_flight: MAX_IN_FLIGHT,
+            snapshot: None,
+            offline_store: OFFLINE_STORE.to_string(),
+            cache: CacheConfig::default(),
+        }
+    }
+}
+
+impl ApiConfig {
+    /// config for an api located at `base_url`, using the default endpoint paths
+    pub fn with_base_url(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into(),
+            ..Default::default()
+        }
+    }
+
+    /// the url of the story id list for this sorting
+    pub fn stories_url(&self, sort: StorySorting) -> String {
+        let stories_api = match sort {
+            StorySorting::Best => &self.best_stories,
+            StorySorting::Top => &self.top_stories,
+            StorySorting::New => &self.new_stories,
+            StorySorting::Show => &self.show_stories,
+            StorySorting::Ask => &self.ask_stories,
+            StorySorting::Job => &self.job_stories,
+        };
+        format!("{}{}", self.base_url, stories_api)
+    }
+
+    pub fn item_url(&self, item_id: i64) -> String {
+        format!("{}{}/{}.json", self.base_url, self.item_api, item_id)
+    }
+
+    pub fn user_url(&self, user_id: &str) -> String {
+        format!("{}{}/{}.json", self.base_url, self.user_api, user_id)
+    }
+
+    /// how often to check for updates, a snapshot never changes so there are no updates to it
+    pub fn live_updates_interval_ms(&self) -> Option<u64> {
+        match self.snapshot {
+            Some(_) => None,
+            None => self.updates_interval_ms,
+        }
+    }
+
+    /// whether the search is available, it goes to hn.algolia.com which a snapshot doesn't include
+    pub fn is_searchable(&self) -> bool {
+        self.snapshot.is_none()
+    }
+
+    pub fn updates_url(&self) -> String {
+        format!("{}{}", self.base_url, self.updates_api)
+    }
+
+    /// read the config from the json content of `<meta name="hn-api-config">`
+    #[cfg(feature = "wasm")]
+    pub fn from_meta_tag() -> Option<Self> {
+        crate::util::meta_json(META_NAME)
+    }
+}
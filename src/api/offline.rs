@@ -0,0 +1,173 @@
+//! Stories saved for offline reading, which outlive the in-memory caches.
+//!
+//! The stories are kept with their comments, keyed by the story id, in an IndexedDB
+//! database in the browser and as json files in a directory on native targets.
+use super::{
+    HnClient,
+    ServerError,
+};
+use crate::types::StoryPageData;
+
+/// save `story` with its loaded comments, returning the copy that was saved
+pub async fn save_story_offline(
+    client: &HnClient,
+    story: &StoryPageData,
+) -> Result<StoryPageData, ServerError> {
+    let mut saved = story.clone();
+    saved.saved_at = Some(chrono::Utc::now());
+    // the new comments are only new relative to when the page was loaded
+    saved.new_kids.clear();
+    let store = OfflineStore::new(&client.config().offline_store);
+    store.put(saved.id, &serde_json::to_string(&saved)?).await?;
+    Ok(saved)
+}
+
+/// the saved copy of the story, if it was saved for offline reading
+pub async fn get_offline_story(
+    client: &HnClient,
+    story_id: i64,
+) -> Result<Option<StoryPageData>, ServerError> {
+    let store = OfflineStore::new(&client.config().offline_store);
+    match store.get(story_id).await? {
+        Some(saved) => Ok(Some(serde_json::from_str(&saved)?)),
+        None => Ok(None),
+    }
+}
+
+/// Where the saved stories are kept, each one as a json string keyed by its id
+struct OfflineStore<'a> {
+    /// the database name in the browser, the directory on native targets
+    location: &'a str,
+}
+
+impl<'a> OfflineStore<'a> {
+    fn new(location: &'a str) -> Self {
+        Self { location }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl OfflineStore<'_> {
+    fn path(&self, id: i64) -> std::path::PathBuf {
+        std::path::Path::new(self.location).join(format!("{}.json", id))
+    }
+
+    async fn put(&self, id: i64, value: &str) -> Result<(), ServerError> {
+        tokio::fs::create_dir_all(self.location).await?;
+        tokio::fs::write(self.path(id), value).await?;
+        Ok(())
+    }
+
+    async fn get(&self, id: i64) -> Result<Option<String>, ServerError> {
+        match tokio::fs::read_to_string(self.path(id)).await {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod indexed_db {
+    use super::{
+        OfflineStore,
+        ServerError,
+    };
+    use sauron::prelude::{
+        js_sys,
+        wasm_bindgen::{
+            closure::Closure,
+            JsCast,
+            JsValue,
+        },
+        wasm_bindgen_futures::JsFuture,
+        web_sys::{
+            Event,
+            IdbDatabase,
+            IdbOpenDbRequest,
+            IdbRequest,
+            IdbTransactionMode,
+        },
+    };
+
+    const STORE_NAME: &str = "stories";
+    const DB_VERSION: u32 = 1;
+
+    fn js_error(e: JsValue) -> ServerError {
+        ServerError::Storage(format!("{:?}", e))
+    }
+
+    /// wait for `request` to complete, resolving to its result
+    async fn completion(request: &IdbRequest) -> Result<JsValue, ServerError> {
+        let promise = js_sys::Promise::new(&mut |resolve, reject| {
+            let on_success = Closure::once_into_js(move |event: Event| {
+                let result = event
+                    .target()
+                    .and_then(|target| target.dyn_into::<IdbRequest>().ok())
+                    .and_then(|request| request.result().ok())
+                    .unwrap_or(JsValue::UNDEFINED);
+                resolve.call1(&JsValue::NULL, &result).ok();
+            });
+            let on_error = Closure::once_into_js(move |event: Event| {
+                reject.call1(&JsValue::NULL, &event).ok();
+            });
+            request.set_onsuccess(Some(on_success.unchecked_ref()));
+            request.set_onerror(Some(on_error.unchecked_ref()));
+        });
+        JsFuture::from(promise).await.map_err(js_error)
+    }
+
+    impl OfflineStore<'_> {
+        async fn open(&self) -> Result<IdbDatabase, ServerError> {
+            let factory = sauron::window()
+                .indexed_db()
+                .map_err(js_error)?
+                .ok_or_else(|| {
+                    ServerError::Storage(String::from("indexeddb is not available"))
+                })?;
+            let request: IdbOpenDbRequest = factory
+                .open_with_u32(self.location, DB_VERSION)
+                .map_err(js_error)?;
+            let on_upgrade = Closure::once_into_js(move |event: Event| {
+                let db = event
+                    .target()
+                    .and_then(|target| target.dyn_into::<IdbRequest>().ok())
+                    .and_then(|request| request.result().ok())
+                    .and_then(|db| db.dyn_into::<IdbDatabase>().ok());
+                if let Some(db) = db {
+                    if let Err(e) = db.create_object_store(STORE_NAME) {
+                        log::error!("unable to create the offline store: {:?}", e);
+                    }
+                }
+            });
+            request.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+            let db = completion(&request).await?;
+            db.dyn_into::<IdbDatabase>().map_err(js_error)
+        }
+
+        pub(super) async fn put(&self, id: i64, value: &str) -> Result<(), ServerError> {
+            let db = self.open().await?;
+            let store = db
+                .transaction_with_str_and_mode(STORE_NAME, IdbTransactionMode::Readwrite)
+                .and_then(|transaction| transaction.object_store(STORE_NAME))
+                .map_err(js_error)?;
+            let request = store
+                .put_with_key(&JsValue::from_str(value), &JsValue::from_f64(id as f64))
+                .map_err(js_error)?;
+            completion(&request).await?;
+            Ok(())
+        }
+
+        pub(super) async fn get(&self, id: i64) -> Result<Option<String>, ServerError> {
+            let db = self.open().await?;
+            let store = db
+                .transaction_with_str(STORE_NAME)
+                .and_then(|transaction| transaction.object_store(STORE_NAME))
+                .map_err(js_error)?;
+            let request = store
+                .get(&JsValue::from_f64(id as f64))
+                .map_err(js_error)?;
+            Ok(completion(&request).await?.as_string())
+        }
+    }
+}
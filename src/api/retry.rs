@@ -0,0 +1,107 @@
+//! Timing out the requests and retrying the ones that failed for a transient reason.
+use super::ServerError;
+use futures::future::{
+    self,
+    Either,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::future::Future;
+
+const MAX_ATTEMPTS: u32 = 3;
+const INITIAL_BACKOFF_MS: u64 = 250;
+const MAX_BACKOFF_MS: u64 = 4_000;
+
+/// How the failed requests are retried.
+///
+/// Only the failures which may go away by themselves are retried,
+/// such as timeouts, dropped connections and 5xx or 429 statuses.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// how many times a request is tried in total, 1 disables the retries
+    pub max_attempts: u32,
+    /// the delay before the first retry, which is doubled for every retry after that
+    pub initial_backoff_ms: u64,
+    /// the delay between the retries doesn't grow past this
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: MAX_ATTEMPTS,
+            initial_backoff_ms: INITIAL_BACKOFF_MS,
+            max_backoff_ms: MAX_BACKOFF_MS,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// the delay before the next attempt, after `attempt` attempts have failed
+    pub fn backoff_ms(&self, attempt: u32) -> u64 {
+        let exponent = attempt.saturating_sub(1).min(16);
+        let backoff = self
+            .initial_backoff_ms
+            .saturating_mul(1 << exponent)
+            .min(self.max_backoff_ms);
+        // half of the delay is random, so the requests that failed together
+        // are not retried all at once
+        let jitter = u64::from(chrono::Utc::now().timestamp_subsec_nanos())
+            % (backoff / 2 + 1);
+        backoff - backoff / 2 + jitter
+    }
+}
+
+impl ServerError {
+    /// whether the request that failed with this error is worth trying again
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ServerError::Reqwest { retryable, .. } => *retryable,
+            ServerError::HttpStatus(status) => is_retryable_status(*status),
+            ServerError::Timeout(_) => true,
+            // there is no point in trying again until the network is back
+            ServerError::Offline => false,
+            ServerError::SerdeJson(_)
+            | ServerError::Io(_)
+            | ServerError::Storage(_)
+            | ServerError::NotFound { .. }
+            | ServerError::NotInSnapshot(_)
+            | ServerError::InvalidSnapshot(_)
+            | ServerError::UnexpectedItem { .. } => false,
+        }
+    }
+}
+
+/// the server is having trouble or asks to slow down, the rest of
+/// the 4xx statuses won't change however many times the request is done
+fn is_retryable_status(status: u16) -> bool {
+    status >= 500 || status == 429 || status == 408
+}
+
+/// wait for `ms` milliseconds, using the timers of the browser in wasm and tokio otherwise
+pub async fn sleep(ms: u64) {
+    #[cfg(target_arch = "wasm32")]
+    sauron::dom::delay(ms.min(i32::MAX as u64) as i32).await;
+    #[cfg(not(target_arch = "wasm32"))]
+    tokio::time::sleep(std::time::Duration::from_millis(ms)).await;
+}
+
+/// run `request`, failing with [`ServerError::Timeout`] if it takes longer than `timeout_ms`
+pub async fn with_timeout<T>(
+    timeout_ms: Option<u64>,
+    request: impl Future<Output = Result<T, ServerError>>,
+) -> Result<T, ServerError> {
+    let timeout_ms = match timeout_ms {
+        Some(timeout_ms) => timeout_ms,
+        None => return request.await,
+    };
+    let request = Box::pin(request);
+    let timeout = Box::pin(sleep(timeout_ms));
+    match future::select(request, timeout).await {
+        Either::Left((result, _)) => result,
+        Either::Right(_) => Err(ServerError::Timeout(timeout_ms)),
+    }
+}
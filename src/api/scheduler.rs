@@ -0,0 +1,155 @@
+//! Limits how many requests are in flight at once, and shares the requests for the same url.
+//!
+//! A story page fans out into dozens of item requests, and the same item may be
+//! requested from several places at once, e.g. as a story preview and as a story page.
+//! Every request of a [`HnClient`](super::HnClient) goes through its scheduler, so an
+//! item which is already being fetched is not fetched a second time.
+use super::ServerError;
+use futures::{
+    channel::oneshot,
+    future::{
+        FutureExt,
+        WeakShared,
+    },
+};
+use std::{
+    collections::{
+        HashMap,
+        VecDeque,
+    },
+    sync::Mutex,
+};
+
+/// The request shared by the concurrent callers for the same url, it is not `Send` in the browser
+#[cfg(target_arch = "wasm32")]
+pub type RequestFuture =
+    futures::future::LocalBoxFuture<'static, Result<String, ServerError>>;
+
+/// The request shared by the concurrent callers for the same url
+#[cfg(not(target_arch = "wasm32"))]
+pub type RequestFuture =
+    futures::future::BoxFuture<'static, Result<String, ServerError>>;
+
+#[derive(Default)]
+pub struct Scheduler {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// the number of requests holding a slot
+    in_flight: usize,
+    /// the requests waiting for a slot, in the order they asked for it
+    waiting: VecDeque<oneshot::Sender<()>>,
+    /// the requests which are running, keyed by their url. They are only kept alive
+    /// by their callers, so a request is dropped when all of its callers are.
+    requests: HashMap<String, WeakShared<RequestFuture>>,
+}
+
+impl Scheduler {
+    fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("scheduler lock poisoned")
+    }
+
+    /// the response of `url`, from the request which is already running for it if
+    /// there is one, otherwise from the request made by `request`
+    pub async fn dedup(
+        &self,
+        url: &str,
+        request: impl FnOnce() -> RequestFuture,
+    ) -> Result<String, ServerError> {
+        let shared = {
+            let mut state = self.state();
+            match state.requests.get(url).and_then(WeakShared::upgrade) {
+                Some(shared) => {
+                    log::trace!("joining the request in flight for {}", url);
+                    shared
+                }
+                None => {
+                    // forget the requests which were dropped before they were done
+                    state.requests.retain(|_, request| request.upgrade().is_some());
+                    let shared = request().shared();
+                    let weak = shared.downgrade().expect("the request is not polled yet");
+                    state.requests.insert(url.to_string(), weak);
+                    shared
+                }
+            }
+        };
+        let result = shared.clone().await;
+        let mut state = self.state();
+        // a new request may have been started for the url once this one was done
+        if state
+            .requests
+            .get(url)
+            .and_then(WeakShared::upgrade)
+            .is_none_or(|running| running.ptr_eq(&shared))
+        {
+            state.requests.remove(url);
+        }
+        result
+    }
+
+    /// wait until fewer than `max_in_flight` requests hold a slot, the slot is
+    /// held until the returned permit is dropped
+    pub async fn acquire(&self, max_in_flight: usize) -> Permit<'_> {
+        let granted = {
+            let mut state = self.state();
+            if state.in_flight < max_in_flight.max(1) {
+                state.in_flight += 1;
+                None
+            } else {
+                let (sender, granted) = oneshot::channel();
+                state.waiting.push_back(sender);
+                Some(granted)
+            }
+        };
+        if let Some(granted) = granted {
+            let mut waiter = Waiter {
+                scheduler: self,
+                granted,
+            };
+            // the sender is only dropped along with the scheduler
+            (&mut waiter.granted).await.ok();
+        }
+        Permit { scheduler: self }
+    }
+
+    /// hand the slot over to the next request waiting for one, or free it
+    fn release(&self) {
+        let mut state = self.state();
+        while let Some(waiting) = state.waiting.pop_front() {
+            // the request may have been dropped while it was waiting
+            if waiting.send(()).is_ok() {
+                return;
+            }
+        }
+        state.in_flight = state.in_flight.saturating_sub(1);
+    }
+}
+
+/// A slot for a request, which is handed over to the next request when dropped
+pub struct Permit<'a> {
+    scheduler: &'a Scheduler,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.scheduler.release();
+    }
+}
+
+/// A request waiting for a slot
+struct Waiter<'a> {
+    scheduler: &'a Scheduler,
+    granted: oneshot::Receiver<()>,
+}
+
+impl Drop for Waiter<'_> {
+    fn drop(&mut self) {
+        // the slot was handed over to a request which was dropped before it got to use it
+        self.granted.close();
+        if let Ok(Some(())) = self.granted.try_recv() {
+            self.scheduler.release();
+        }
+    }
+}
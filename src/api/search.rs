@@ -0,0 +1,181 @@
+//! Full-text search through an algolia compatible hackernews search api,
+//! the format is described in <https://hn.algolia.com/api>
+use super::{
+    make_json_get_request,
+    HnClient,
+    ServerError,
+};
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use url::form_urlencoded;
+
+/// A search request
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SearchQuery {
+    /// the full-text query
+    pub query: String,
+    /// restrict the results to these tags, ie: `story`, `author_pg`, `(story,poll)`
+    pub tags: Option<String>,
+    /// filter on the numerical attributes, ie: `points>100,created_at_i>1600000000`
+    pub numeric_filters: Option<String>,
+    /// the page of the results, starting at 0 the same way the api does
+    pub page: usize,
+    pub hits_per_page: Option<usize>,
+}
+
+impl SearchQuery {
+    pub fn new(query: impl Into<String>) -> Self {
+        Self {
+            query: query.into(),
+            ..Default::default()
+        }
+    }
+
+    /// the same query, for the next page of results
+    pub fn next_page(&self) -> Self {
+        Self {
+            page: self.page + 1,
+            ..self.clone()
+        }
+    }
+
+    /// the query parameters as named in the search api
+    fn params(&self) -> Vec<(&str, String)> {
+        let mut params = vec![("query", self.query.clone())];
+        if let Some(tags) = &self.tags {
+            params.push(("tags", tags.clone()));
+        }
+        if let Some(numeric_filters) = &self.numeric_filters {
+            params.push(("numericFilters", numeric_filters.clone()));
+        }
+        if self.page > 0 {
+            params.push(("page", self.page.to_string()));
+        }
+        if let Some(hits_per_page) = self.hits_per_page {
+            params.push(("hitsPerPage", hits_per_page.to_string()));
+        }
+        params
+    }
+
+    /// the url of the search api for this query
+    pub fn api_url(&self, search_url: &str) -> String {
+        let params = form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.params())
+            .finish();
+        format!("{}/search?{}", search_url, params)
+    }
+
+    /// the query string of this search in the app url, ie: `q=rust&page=2`
+    pub fn to_query(&self) -> String {
+        form_urlencoded::Serializer::new(String::new())
+            .extend_pairs(self.params().into_iter().map(|(key, value)| {
+                if key == "query" {
+                    ("q", value)
+                } else {
+                    (key, value)
+                }
+            }))
+            .finish()
+    }
+
+    /// parse the search from the query string of the app url
+    pub fn from_query(params: &str) -> Option<Self> {
+        let mut search = SearchQuery::default();
+        for (key, value) in form_urlencoded::parse(params.as_bytes()) {
+            match &*key {
+                "query" | "q" => search.query = value.into_owned(),
+                "tags" => search.tags = Some(value.into_owned()),
+                "numericFilters" => {
+                    search.numeric_filters = Some(value.into_owned())
+                }
+                "page" => search.page = value.parse().ok()?,
+                "hitsPerPage" => search.hits_per_page = value.parse().ok(),
+                _ => (),
+            }
+        }
+        Some(search)
+    }
+}
+
+/// A page of results of the search api
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResults {
+    /// the search that yields these results
+    #[serde(default)]
+    pub search: SearchQuery,
+    pub hits: Vec<SearchHit>,
+    pub nb_hits: usize,
+    pub page: usize,
+    pub nb_pages: usize,
+    pub hits_per_page: usize,
+}
+
+impl SearchResults {
+    /// the position of the first hit of this page in all the results
+    pub fn offset(&self) -> usize {
+        self.page.saturating_mul(self.hits_per_page)
+    }
+
+    pub fn has_more(&self) -> bool {
+        self.page + 1 < self.nb_pages
+    }
+}
+
+/// A story or comment that matched the search
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SearchHit {
+    #[serde(rename = "objectID")]
+    pub object_id: String,
+    pub title: Option<String>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub author: String,
+    pub points: Option<i64>,
+    pub num_comments: Option<i64>,
+    pub story_text: Option<String>,
+    pub comment_text: Option<String>,
+    /// the story a comment belongs to
+    pub story_id: Option<i64>,
+    pub story_title: Option<String>,
+    /// the creation time, in seconds since the epoch
+    pub created_at_i: i64,
+    #[serde(default, rename = "_tags")]
+    pub tags: Vec<String>,
+}
+
+impl SearchHit {
+    /// the item id of this hit
+    pub fn id(&self) -> Option<i64> {
+        self.object_id.parse().ok()
+    }
+
+    pub fn is_comment(&self) -> bool {
+        self.comment_text.is_some()
+            || self.tags.iter().any(|tag| tag == "comment")
+    }
+
+    pub fn time(&self) -> DateTime<Utc> {
+        Utc.timestamp_opt(self.created_at_i, 0)
+            .single()
+            .unwrap_or_default()
+    }
+}
+
+pub async fn get_search_results(
+    client: &HnClient,
+    search: &SearchQuery,
+) -> Result<SearchResults, ServerError> {
+    let url = search.api_url(&client.config().search_url);
+    let mut results =
+        make_json_get_request::<SearchResults>(client, &url).await?;
+    results.search = search.clone();
+    Ok(results)
+}
@@ -0,0 +1,150 @@
+//! A static snapshot of the api, for hosting the app where there is no api access.
+//!
+//! A snapshot is a directory with the same layout as the firebase api, e.g. `topstories.json`,
+//! `item/8863.json` and `user/pg.json`, along with a `manifest.json` describing it.
+//! Snapshots are written by the `export` binary and read with a [`SnapshotTransport`].
+use super::{
+    transport::TransportFuture,
+    HnTransport,
+    ServerError,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// the version of the snapshot layout, bumped whenever it changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// the file name of the manifest, at the root of the snapshot
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// What a snapshot contains and where it is taken from
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Manifest {
+    pub version: u32,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub created_at: DateTime<Utc>,
+    /// the base url of the api the snapshot is taken from
+    pub source: String,
+    /// the number of stories in each of the story lists
+    pub stories: Vec<(String, usize)>,
+    /// the number of items, that is stories, comments and poll options
+    pub items: usize,
+    pub users: usize,
+}
+
+/// A transport which reads the responses from a snapshot instead of the api.
+///
+/// The urls are resolved by replacing the base url of the api with the location of
+/// the snapshot, which is a directory on native targets and a url in the browser.
+/// The items and users which are not in the snapshot resolve to `null`, and
+/// the urls of other apis, such as the search, fail with [`ServerError::NotInSnapshot`].
+/// The manifest is checked on the first request, and every request fails with
+/// [`ServerError::InvalidSnapshot`] when the snapshot is incomplete or of another version.
+#[derive(Debug, Clone)]
+pub struct SnapshotTransport {
+    location: String,
+    base_url: String,
+    /// the outcome of checking the manifest, once it could be read
+    checked: Arc<Mutex<Option<Result<(), ServerError>>>>,
+    #[cfg(target_arch = "wasm32")]
+    client: reqwest::Client,
+}
+
+impl SnapshotTransport {
+    /// read the snapshot at `location` in place of the api at `base_url`
+    pub fn new(location: impl Into<String>, base_url: impl Into<String>) -> Self {
+        Self {
+            location: location.into(),
+            base_url: base_url.into(),
+            checked: Arc::new(Mutex::new(None)),
+            #[cfg(target_arch = "wasm32")]
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// the path of `url` relative to the root of the snapshot,
+    /// which only has the urls of the api at `base_url`
+    fn snapshot_path<'a>(&self, url: &'a str) -> Result<&'a str, ServerError> {
+        url.strip_prefix(self.base_url.as_str())
+            .map(|path| path.trim_start_matches('/'))
+            .ok_or_else(|| ServerError::NotInSnapshot(url.to_string()))
+    }
+
+    /// check that the snapshot was fully exported, in the layout this app reads.
+    /// The manifest is written last, so a snapshot without one is incomplete
+    async fn check_manifest(&self) -> Result<(), ServerError> {
+        if let Some(checked) = self.checked.lock().expect("snapshot lock poisoned").clone() {
+            return checked;
+        }
+        // the failures to read the manifest are not kept, they may be transient
+        let checked = match self.read(MANIFEST_FILE).await? {
+            None => Err(ServerError::InvalidSnapshot(format!(
+                "{} has no {}, it is not fully exported",
+                self.location, MANIFEST_FILE
+            ))),
+            Some(body) => match serde_json::from_str::<Manifest>(&body) {
+                Ok(manifest) if manifest.version == SNAPSHOT_VERSION => Ok(()),
+                Ok(manifest) => Err(ServerError::InvalidSnapshot(format!(
+                    "{} is a version {} snapshot, while version {} is expected",
+                    self.location, manifest.version, SNAPSHOT_VERSION
+                ))),
+                Err(e) => Err(ServerError::InvalidSnapshot(format!(
+                    "the {} of {} is invalid: {}",
+                    MANIFEST_FILE, self.location, e
+                ))),
+            },
+        };
+        *self.checked.lock().expect("snapshot lock poisoned") = Some(checked.clone());
+        checked
+    }
+
+    /// the file at `path` in the snapshot, `None` if there is no such file
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn read(&self, path: &str) -> Result<Option<String>, ServerError> {
+        let path = std::path::Path::new(&self.location).join(path);
+        match tokio::fs::read_to_string(path).await {
+            Ok(body) => Ok(Some(body)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// the file at `path` in the snapshot, `None` if there is no such file.
+    /// reqwest only takes absolute urls, so a relative location is resolved against
+    /// the base url of the document, which is the root of the app in every router mode
+    #[cfg(target_arch = "wasm32")]
+    async fn read(&self, path: &str) -> Result<Option<String>, ServerError> {
+        let base_uri = sauron::document().base_uri().ok().flatten().unwrap_or_default();
+        let location = format!("{}/", self.location.trim_end_matches('/'));
+        let snapshot_url = url::Url::parse(&base_uri)
+            .and_then(|base_uri| base_uri.join(&location))
+            .and_then(|root| root.join(path))
+            .map(|snapshot_url| snapshot_url.to_string())
+            .unwrap_or_else(|_| format!("{}{}", location, path));
+        let response = self.client.get(&snapshot_url).send().await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.text().await?))
+    }
+}
+
+impl HnTransport for SnapshotTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let path = self.snapshot_path(url)?;
+            self.check_manifest().await?;
+            Ok(self.read(path).await?.unwrap_or_else(|| String::from("null")))
+        })
+    }
+}
@@ -0,0 +1,95 @@
+//! The transports which the [`HnClient`](super::HnClient) uses to talk to the api.
+//!
+//! A transport only knows how to fetch the body of a url, everything else
+//! (building the urls, decoding the json) is done by the api functions.
+use super::ServerError;
+use std::{
+    collections::HashMap,
+    sync::RwLock,
+};
+
+/// The future returned by a transport, it is not `Send` in the browser
+#[cfg(target_arch = "wasm32")]
+pub type TransportFuture<'a> =
+    futures::future::LocalBoxFuture<'a, Result<String, ServerError>>;
+
+/// The future returned by a transport
+#[cfg(not(target_arch = "wasm32"))]
+pub type TransportFuture<'a> =
+    futures::future::BoxFuture<'a, Result<String, ServerError>>;
+
+/// Fetch the body of a GET request
+pub trait HnTransport: Send + Sync {
+    /// return the response body of `url`
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a>;
+}
+
+/// The transport used by default, which does the actual http request using reqwest.
+/// It has no timeout of its own, the timeout of the `ApiConfig` is applied to every request
+#[derive(Debug, Default, Clone)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl HnTransport for ReqwestTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(async move {
+            #[cfg(target_arch = "wasm32")]
+            if !sauron::window().navigator().on_line() {
+                return Err(ServerError::Offline);
+            }
+            let response = self.client.get(url).send().await?;
+            let status = response.status();
+            if !status.is_success() {
+                return Err(ServerError::HttpStatus(status.as_u16()));
+            }
+            Ok(response.text().await?)
+        })
+    }
+}
+
+/// An in-memory transport which serves canned responses keyed by url.
+///
+/// Urls that have no response registered resolves to `null`, the same
+/// way the firebase api does for items and users that does not exist.
+#[derive(Debug, Default)]
+pub struct FixtureTransport {
+    responses: RwLock<HashMap<String, String>>,
+}
+
+impl FixtureTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// serve `body` for every request to `url`
+    pub fn insert(&self, url: impl Into<String>, body: impl Into<String>) {
+        self.responses
+            .write()
+            .expect("fixture lock poisoned")
+            .insert(url.into(), body.into());
+    }
+
+    /// serve the json serialization of `value` for every request to `url`
+    pub fn insert_json<T: serde::Serialize>(
+        &self,
+        url: impl Into<String>,
+        value: &T,
+    ) -> Result<(), ServerError> {
+        self.insert(url, serde_json::to_string(value)?);
+        Ok(())
+    }
+}
+
+impl HnTransport for FixtureTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        let body = self
+            .responses
+            .read()
+            .expect("fixture lock poisoned")
+            .get(url)
+            .cloned()
+            .unwrap_or_else(|| String::from("null"));
+        Box::pin(async move { Ok(body) })
+    }
+}
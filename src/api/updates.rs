@@ -0,0 +1,68 @@
+//! Polling of the recently changed items, used for patching the displayed
+//! content in place instead of reloading it.
+use super::{
+    get_item,
+    make_json_get_request,
+    HnClient,
+    ServerError,
+};
+use crate::types::{
+    Comment,
+    StoryItem,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// The items and profiles that changed recently
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Updates {
+    #[serde(default)]
+    pub items: Vec<i64>,
+    #[serde(default)]
+    pub profiles: Vec<String>,
+}
+
+pub async fn get_updates(client: &HnClient) -> Result<Updates, ServerError> {
+    let url = client.config().updates_url();
+    make_json_get_request::<Updates>(client, &url).await
+}
+
+/// fetch the current state of a story, bypassing the cache
+pub async fn get_story_update(
+    client: &HnClient,
+    story_id: i64,
+) -> Result<StoryItem, ServerError> {
+    let item = get_item(client, story_id).await?;
+    let kind = item.kind();
+    let story = item
+        .into_story()
+        .ok_or(ServerError::UnexpectedItem { id: story_id, kind })?;
+
+    // the full story is out of date as well, it is refetched when it is opened again
+    #[cfg(feature = "caching")]
+    {
+        client.caches.stories.remove(&story_id);
+        client.caches.story_previews.put(story_id, story.clone());
+    }
+
+    Ok(story)
+}
+
+/// fetch the current state of a comment without its replies, bypassing the cache
+pub async fn get_comment_update(
+    client: &HnClient,
+    comment_id: i64,
+) -> Result<Comment, ServerError> {
+    let item = get_item(client, comment_id).await?;
+    let kind = item.kind();
+    let comment = item
+        .into_comment()
+        .ok_or(ServerError::UnexpectedItem { id: comment_id, kind })?;
+
+    #[cfg(feature = "caching")]
+    client.caches.comments.put(comment_id, comment.clone());
+
+    Ok(comment)
+}
@@ -1,35 +1,120 @@
 use crate::types::{
-    Comment, StoryItem, StoryPageData, StorySorting, UserData,
+    Comment, Item, StoryItem, StoryList, StoryOrder, StoryPageData, StorySorting,
+    UserData,
 };
 pub use content::Content;
+use crate::route::{
+    Route,
+    Router,
+    UnknownRoute,
+};
+#[cfg(feature = "wasm")]
+use history::History;
+#[cfg(feature = "wasm")]
+use navigation::Navigation;
+pub use settings::Settings;
 use sauron::prelude::*;
 use serde::{Deserialize, Serialize};
 #[cfg(feature = "wasm")]
 use crate::api;
-use crate::api::ServerError;
+#[cfg(feature = "wasm")]
+use crate::api::Cached;
+#[cfg(feature = "wasm")]
+use std::future::Future;
+use crate::api::{
+    ApiConfig,
+    Fetched,
+    HnClient,
+    SearchQuery,
+    SearchResults,
+    ServerError,
+    Updates,
+};
 
 mod content;
+#[cfg(feature = "wasm")]
+mod history;
+#[cfg(feature = "wasm")]
+mod navigation;
+mod settings;
+
+/// a restored page is scrolled to where it was left once it is rendered,
+/// which is checked this many times, this many milliseconds apart
+#[cfg(feature = "wasm")]
+const RESTORE_SCROLL_ATTEMPTS: usize = 10;
+#[cfg(feature = "wasm")]
+const RESTORE_SCROLL_INTERVAL_MS: i32 = 20;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FetchStatus<T> {
     Idle,
     Loading,
     Complete(T),
-    Error(String),
+    Error(ServerError),
 }
 
+#[derive(Debug, Clone)]
 pub enum Msg {
     FetchStories,
     FetchStoriesSorted(StorySorting),
+    /// show this page of the story listing
+    FetchStoriesPage(StorySorting, usize),
+    /// show the next page of the story listing currently displayed
+    LoadMoreStories,
+    /// re-sort the displayed stories
+    ReorderStories(StoryOrder),
     OpenStory(i64),
+    /// save the displayed story with its loaded comments for offline reading
+    SaveOffline(i64),
+    /// the copy of the story that was saved for offline reading
+    SavedOffline(StoryPageData),
     /// show the user data of this username
     ShowUserPage(String),
     ShowCommentPermalink(i64),
+    /// show the page of this route, such as the one suggested for a url which is not a route
+    ShowRoute(Route),
+    /// load the replies of this story or comment which are not in the comment tree yet
+    LoadMoreReplies(i64),
+    /// the parent id and its newly loaded replies
+    ReceivedReplies(i64, Vec<Comment>),
+    /// fetch again the items of the displayed content which failed to load
+    RetryFailed,
+    /// the items which were fetched again
+    ReceivedRetried(Fetched<Item>),
+    /// load the comments posted to this story since it was displayed
+    LoadNewComments(i64),
+    /// check if the displayed content has changed
+    PollUpdates,
+    ReceivedUpdates(Updates),
+    /// the latest state of a displayed story
+    PatchStory(StoryItem),
+    /// the latest state of a displayed comment
+    PatchComment(Comment),
+    /// show or hide the content of the dead comments
+    ToggleShowDead,
+    /// the text typed in the search box
+    SearchInput(String),
+    /// search for the text in the search box
+    SubmitSearch,
+    Search(SearchQuery),
     /// the new url and the Content
     ReceivedContent(Content),
+    /// the content saved for offline reading, shown when the api could not be reached
+    ReceivedOfflineContent(Content),
+    /// a fresh copy of the content that was displayed from a stale cache
+    RevalidatedContent(Content),
     RequestError(ServerError),
+    /// dispatch again the navigation which failed
+    Retry,
+    /// hide the error of the navigation which failed while its previous page was kept
+    DismissToast,
     /// the new url
     UrlChanged(String),
+    /// the url and the key of the history entry which was gone back or forward to
+    PoppedState(String, Option<u64>),
+    /// a message of the navigation with this generation, which is
+    /// dropped if another navigation has started since
+    Navigated(u64, Box<Msg>),
     NoOp,
 }
 
@@ -38,12 +123,62 @@ pub enum Msg {
 pub struct App {
     /// the content to be displayed in out app
     pub content: FetchStatus<Content>,
+    /// the order stories are displayed in, kept when navigating between listings
+    #[serde(default)]
+    pub story_order: StoryOrder,
+    /// the text in the search box
+    #[serde(default)]
+    pub search_input: String,
+    /// the display preferences, which are restored from the local storage on init
+    #[serde(default)]
+    pub settings: Settings,
+    /// the content is the copy saved for offline reading, as the api could not be reached
+    #[serde(default)]
+    pub offline: bool,
+    /// the content of a navigation is being fetched, the current
+    /// content stays displayed, dimmed, until the new one arrives
+    #[serde(skip)]
+    pending: bool,
+    /// the error of the last navigation, displayed over the page which was kept in its place
+    #[serde(skip)]
+    toast: Option<ServerError>,
+    /// how the pages of the app are put in the url
+    #[serde(default)]
+    pub router: Router,
+    /// the last navigation, which is dispatched again when retrying after an error
+    #[serde(skip)]
+    #[cfg_attr(not(feature = "wasm"), allow(dead_code))]
+    last_navigation: Option<Msg>,
+    /// the navigation in progress, the content of the ones before it is not displayed
+    #[cfg(feature = "wasm")]
+    #[serde(skip)]
+    navigation: Navigation,
+    /// the pages left through the history, which are restored when going back to them
+    #[cfg(feature = "wasm")]
+    #[serde(skip)]
+    history: History,
+    /// the client used to fetch the content, only its config is serialized
+    #[serde(default, rename = "api_config")]
+    client: HnClient,
 }
 
 impl Default for App {
     fn default() -> Self {
         Self {
             content: FetchStatus::Idle,
+            story_order: StoryOrder::default(),
+            search_input: String::new(),
+            settings: Settings::default(),
+            offline: false,
+            pending: false,
+            toast: None,
+            router: Router::default(),
+            last_navigation: None,
+            #[cfg(feature = "wasm")]
+            navigation: Navigation::default(),
+            #[cfg(feature = "wasm")]
+            history: History::default(),
+            client: HnClient::default(),
         }
     }
 }
@@ -54,33 +189,34 @@ impl Application for App {
 
     #[cfg(feature = "wasm")]
     fn init(&mut self) -> Cmd<Msg> {
-        // Handle initial load by checking current hash/pathname
-        let location = sauron::window().location();
-        let hash = location.hash().unwrap_or_default();
-        let pathname = location.pathname().unwrap_or_default();
-        let url = if hash.is_empty() { pathname } else { hash.clone() };
-        
+        if let Some(settings) = Settings::load() {
+            self.settings = settings;
+        }
+        self.init_history();
+        // Handle initial load by routing the current location
+        let url = current_location();
+        let has_hash = !sauron::window().location().hash().unwrap_or_default().is_empty();
+        let poll_updates = match self.client.config().live_updates_interval_ms() {
+            Some(interval_ms) => {
+                Time::every(interval_ms as i32, || Msg::PollUpdates)
+            }
+            None => Cmd::none(),
+        };
+
         Cmd::batch([
+            poll_updates,
             // Use popstate for now, we'll handle hash changes through URL monitoring
-            Window::on_popstate(|_e| {
+            Window::on_popstate(|e| {
                 log::trace!("popstate is triggered in sauron add event listener");
-                let location = sauron::window().location();
-                let hash = location.hash().unwrap_or_default();
-                let pathname = location.pathname().unwrap_or_default();
-                let url = if hash.is_empty() { pathname } else { hash };
-                Msg::UrlChanged(url)
+                let key = e.state().as_f64().map(|key| key as u64);
+                Msg::PoppedState(current_location(), key)
             }),
-            // Handle initial routing - for SPAs with hash routing, empty hash means root
-            if !hash.is_empty() {
+            // Handle initial routing, the content rendered by the server is kept
+            // unless the hash points to another page
+            if has_hash || matches!(self.content, FetchStatus::Idle) {
                 Cmd::new(async move { Msg::UrlChanged(url) })
             } else {
-                // No hash means we're at the app root, fetch stories
-                match self.content{
-                    FetchStatus::Idle => {
-                        self.fetch_stories()
-                    }
-                    _ => Cmd::none(),
-                }
+                Cmd::none()
             },
         ])
     }
@@ -89,14 +225,14 @@ impl Application for App {
         node! {
             <body class="main-layout">
                 <header>
-                   <a relative href="/"
+                   <a relative href=self.router.href(&Route::Home)
                         on_click=|e|{
                             e.prevent_default();
                             Msg::FetchStories
                         }>
                        <div class="yc-logo">"Y"</div>
                    </a>
-                   <a relative href="/"
+                   <a relative href=self.router.href(&Route::Home)
                         on_click=|e|{
                             e.prevent_default();
                             Msg::FetchStories
@@ -104,6 +240,7 @@ impl Application for App {
                         <h1>"Hacker News"</h1>
                    </a>
                    { self.view_story_sorting() }
+                   { self.view_search_box() }
                    <nav class="right-nav">
                         <a href="https://github.com/ivanceras/hackernews-sauron">
                            <svg role="img" xmlns="http://www.w3.org/2000/svg">
@@ -113,11 +250,14 @@ impl Application for App {
                    </nav>
                 </header>
                     { self.view_loader() }
-                <main class="content">
+                    { self.view_offline_indicator() }
+                <main class=if self.pending { "content pending" } else { "content" }>
                     { self.view_content() }
                 </main>
+                { self.view_toast() }
                 <footer>
                     <hr/>
+                    { self.view_settings() }
                     <nav class="repo-link">
                    "Powered by "<a href="https://github.com/ivanceras/sauron" target="_blank" rel="noopener noreferrer">"Sauron"</a>
                    </nav>
@@ -133,78 +273,11 @@ impl Application for App {
 
     #[cfg(feature = "wasm")]
     fn update(&mut self, msg: Msg) -> Cmd<Msg> {
-        match msg {
-            Msg::FetchStories => {
-                Self::push_state_url("/");
-                self.content = FetchStatus::Loading;
-                self.fetch_stories()
-            }
-            Msg::FetchStoriesSorted(sorting) => {
-                Self::push_state_url(&sorting.to_url());
-                self.content = FetchStatus::Loading;
-                self.fetch_stories_with_sorting(sorting)
-            }
-            Msg::OpenStory(story_id) => {
-                Self::push_state_url(&StoryItem::to_url(story_id));
-                self.content = FetchStatus::Loading;
-                self.fetch_story_page(story_id)
-            }
-            Msg::ShowUserPage(username) => {
-                Self::push_state_url(&UserData::to_url(&username));
-                self.content = FetchStatus::Loading;
-                log::trace!("showing user: {}", username);
-                self.fetch_user_page(username)
-            }
-            Msg::ShowCommentPermalink(comment_id) => {
-                Self::push_state_url(&Comment::to_url(comment_id));
-                self.content = FetchStatus::Loading;
-                log::trace!("showing comment: {}", comment_id);
-                self.fetch_comment_permalink(comment_id)
-            }
-            Msg::ReceivedContent(content) => {
-                self.content = FetchStatus::Complete(content);
-                Cmd::from(Window::scroll_to_top(Msg::NoOp))
-            }
-            Msg::RequestError(server_error) => {
-                log::error!("Error: {}", server_error);
-                self.content = FetchStatus::Error(server_error.to_string());
-                Cmd::none()
-            }
-            Msg::UrlChanged(url) => {
-                self.content = FetchStatus::Loading;
-                log::trace!("url changed to: {}", url);
-                
-                // Extract hash from URL - for hash routing, we only care about the hash part
-                let hash = if let Some(hash_part) = url.split('#').nth(1) {
-                    format!("#{}", hash_part)
-                } else {
-                    // If no hash, treat as root
-                    String::new()
-                };
-                
-                let cmd = if let Some(sorting) = StorySorting::from_url(&hash) {
-                    self.fetch_stories_with_sorting(sorting)
-                } else if let Some(story_id) = StoryItem::id_from_url(&hash) {
-                    self.fetch_story_page(story_id)
-                } else if let Some(comment_id) = Comment::id_from_url(&hash) {
-                    self.fetch_comment_permalink(comment_id)
-                } else if let Some(username) = UserData::id_from_url(&hash) {
-                    self.fetch_user_page(username)
-                } else if hash.is_empty() || hash == "#" {
-                    // Empty hash or just "#" means root - fetch stories
-                    self.fetch_stories()
-                } else {
-                    log::trace!("No appropriate route found for hash: {}", hash);
-                    Cmd::none()
-                };
-
-                Cmd::batch(vec![
-                    cmd,
-                    Cmd::from(Window::scroll_to_top(Msg::NoOp)),
-                ])
-            }
-            Msg::NoOp => Cmd::none(),
+        if msg.is_navigation() {
+            self.last_navigation = Some(msg.clone());
+            return self.navigate(|app| app.update_content(msg));
         }
+        self.update_content(msg)
     }
 }
 
@@ -213,22 +286,68 @@ impl App {
         match &self.content {
             FetchStatus::Idle => node! { <p>"Waiting around..."</p> },
             FetchStatus::Loading => node! { <p>"Loading..."</p> },
-            FetchStatus::Error(e) => {
-                node! {
-                    <article>
-                        <p>"Okay, something went wrong. I think it was: "</p>
-                        <code>{text(e)}</code>
-                    </article>
-                }
-            }
-            FetchStatus::Complete(content) => content.view(),
+            FetchStatus::Error(e) => self.view_error(e),
+            FetchStatus::Complete(content) => content.view(&self.settings, &self.router),
+        }
+    }
+
+    fn view_error(&self, error: &ServerError) -> Node<Msg> {
+        match error {
+            ServerError::NotFound { kind, id } => node! {
+                <article class="error not-found">
+                    <h2>{text!("No such {}", kind)}</h2>
+                    <p>{text!("There is no {} {} on Hacker News, it may never have existed.", kind, id)}</p>
+                    <a relative href=self.router.href(&Route::Home)
+                        on_click=|e|{
+                            e.prevent_default();
+                            Msg::FetchStories
+                        }>"Back to the stories"</a>
+                </article>
+            },
+            ServerError::Offline => node! {
+                <article class="error offline">
+                    <h2>"You are offline"</h2>
+                    <p>"Check your network connection and try again."</p>
+                    { Self::view_retry_button() }
+                </article>
+            },
+            ServerError::Timeout(timeout_ms) => node! {
+                <article class="error timeout">
+                    <h2>"Hacker News is taking too long to respond"</h2>
+                    <p>{text!("The request was given up after {}.", crate::util::duration(*timeout_ms))}</p>
+                    { Self::view_retry_button() }
+                </article>
+            },
+            ServerError::HttpStatus(status) => node! {
+                <article class="error http-status">
+                    <h2>"Hacker News could not be reached"</h2>
+                    <p>{text!("The server responded with status {}.", status)}</p>
+                    { Self::view_retry_button() }
+                </article>
+            },
+            e => node! {
+                <article class="error">
+                    <p>"Okay, something went wrong. I think it was: "</p>
+                    <code>{text(e)}</code>
+                    { Self::view_retry_button() }
+                </article>
+            },
+        }
+    }
+
+    fn view_retry_button() -> Node<Msg> {
+        node! {
+            <button class="retry" on_click=|_| Msg::Retry>"Try again"</button>
         }
     }
 
     fn view_story_sorting(&self) -> Node<Msg>{
+        let is_story_list =
+            matches!(self.content, FetchStatus::Complete(Content::Stories(_)));
         nav([class("story-sort")],
             StorySorting::all().into_iter().map(|sorting|{
-                a([href(format!("/{}",sorting.to_str())),
+                let route = Route::Stories { sorting, page: 1 };
+                a([href(self.router.href(&route)),
                     on_click(move|e|{
                         e.prevent_default();
                         Msg::FetchStoriesSorted(sorting)
@@ -236,14 +355,94 @@ impl App {
                     [text!("{}", sorting.to_str())]
                  )
             })
+            .chain(is_story_list.then(|| self.view_story_order()))
+        )
+    }
+
+    fn view_story_order(&self) -> Node<Msg>{
+        span([class("story-order")],
+            std::iter::once(text("sort by:")).chain(
+                StoryOrder::all().into_iter().map(|order|{
+                    a([href("#"),
+                        classes_flag([("selected", order == self.story_order)]),
+                        on_click(move|e|{
+                            e.prevent_default();
+                            Msg::ReorderStories(order)
+                        })],
+                        [text!("{}", order.to_str())]
+                     )
+                })
+            )
+        )
+    }
+
+    fn view_search_box(&self) -> Node<Msg> {
+        if !self.client.config().is_searchable() {
+            return text("");
+        }
+        node! {
+            <form class="search"
+                on_submit=|e|{
+                    e.prevent_default();
+                    Msg::SubmitSearch
+                }>
+                <input type="search" placeholder="Search" value=&self.search_input
+                    on_input=|e|{
+                        Msg::SearchInput(e.value())
+                    }/>
+            </form>
+        }
+    }
+
+    fn view_settings(&self) -> Node<Msg>{
+        nav([class("settings")],
+            [label([], [
+                input([r#type("checkbox"),
+                    checked(self.settings.show_dead),
+                    on_click(|_| Msg::ToggleShowDead)],
+                    []
+                ),
+                text("showdead"),
+            ])]
         )
     }
 
+    /// the error of the navigation which failed, the page before it is still displayed
+    fn view_toast(&self) -> Node<Msg> {
+        match &self.toast {
+            Some(error) => node! {
+                <div class="toast" role="alert">
+                    <span>{text!("The page could not be loaded: {}", error)}</span>
+                    { Self::view_retry_button() }
+                    <button class="dismiss" on_click=|_| Msg::DismissToast>"Dismiss"</button>
+                </div>
+            },
+            None => text(""),
+        }
+    }
+
+    fn view_offline_indicator(&self) -> Node<Msg> {
+        let saved_at = match &self.content {
+            FetchStatus::Complete(Content::StoryPage(story_page)) if self.offline => {
+                story_page.saved_at
+            }
+            _ => None,
+        };
+        match saved_at {
+            Some(saved_at) => node! {
+                <div class="offline-indicator">
+                    { text!("You are offline, this is the copy saved {} ago", crate::util::time_ago(saved_at)) }
+                </div>
+            },
+            None => text(""),
+        }
+    }
+
     fn view_loader(&self) -> Node<Msg> {
         node! {
             <div id="loader">
             {
-                if matches!(self.content, FetchStatus::Loading) {
+                if self.pending || matches!(self.content, FetchStatus::Loading) {
                     node!{
                         <div>
                             <div class="line"></div>
@@ -262,38 +461,444 @@ impl App {
 }
 
 impl App {
-    pub fn with_stories(stories: Vec<StoryItem>) -> Self {
+    /// create an app which fetches its content through `client`
+    pub fn with_client(client: HnClient) -> Self {
         Self {
-            content: FetchStatus::Complete(Content::from(stories)),
+            client,
+            ..Default::default()
+        }
+    }
+
+    /// point the app to the api described by `config`
+    pub fn set_api_config(&mut self, config: ApiConfig) {
+        self.client = HnClient::from(config);
+    }
+
+    /// put the pages of the app in the url as set by `router`
+    pub fn set_router(&mut self, router: Router) {
+        self.router = router;
+    }
+
+    pub fn with_stories(story_list: StoryList) -> Self {
+        Self {
+            content: FetchStatus::Complete(Content::from(story_list)),
+            ..Default::default()
         }
     }
     pub fn with_story(story_page: StoryPageData) -> Self {
         Self {
             content: FetchStatus::Complete(Content::from(story_page)),
+            ..Default::default()
         }
     }
     pub fn with_user_page(user_data: UserData) -> Self {
         Self {
             content: FetchStatus::Complete(Content::from(user_data)),
+            ..Default::default()
         }
     }
 
     pub fn with_comment_permalink(comment: Comment) -> Self {
         Self {
             content: FetchStatus::Complete(Content::from(comment)),
+            ..Default::default()
         }
     }
+
+    /// show that `unknown` is not a page of the app
+    pub fn with_unknown_route(unknown: UnknownRoute) -> Self {
+        Self {
+            content: FetchStatus::Complete(Content::from(unknown)),
+            ..Default::default()
+        }
+    }
+
+    pub fn with_search_results(results: SearchResults) -> Self {
+        Self {
+            search_input: results.search.query.clone(),
+            content: FetchStatus::Complete(Content::from(results)),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl Msg {
+    /// whether this message shows another page, which is what is retried after an error
+    fn is_navigation(&self) -> bool {
+        matches!(
+            self,
+            Msg::FetchStories
+                | Msg::FetchStoriesSorted(_)
+                | Msg::FetchStoriesPage(_, _)
+                | Msg::LoadMoreStories
+                | Msg::OpenStory(_)
+                | Msg::ShowUserPage(_)
+                | Msg::ShowCommentPermalink(_)
+                | Msg::ShowRoute(_)
+                | Msg::Search(_)
+                | Msg::UrlChanged(_)
+                | Msg::PoppedState(_, _)
+        )
+    }
 }
 
 #[cfg(feature = "wasm")]
 impl App{
 
+    /// start a new navigation with the command of `cmd`, the messages of the
+    /// navigations before it are dropped so only the latest one is displayed
+    fn navigate(&mut self, cmd: impl FnOnce(&mut Self) -> Cmd<Msg>) -> Cmd<Msg> {
+        let generation = self.navigation.start();
+        cmd(self).map_msg(move |msg| Msg::Navigated(generation, Box::new(msg)))
+    }
+
+    fn update_content(&mut self, msg: Msg) -> Cmd<Msg> {
+        match msg {
+            Msg::FetchStories => {
+                self.push_route(&Route::Home);
+                self.start_loading();
+                self.fetch_stories()
+            }
+            Msg::FetchStoriesSorted(sorting) => {
+                self.push_route(&Route::Stories { sorting, page: 1 });
+                self.start_loading();
+                self.fetch_stories_with_sorting(sorting)
+            }
+            Msg::FetchStoriesPage(sorting, page) => {
+                self.push_route(&Route::Stories { sorting, page });
+                self.start_loading();
+                self.fetch_stories_page(sorting, page)
+            }
+            Msg::LoadMoreStories => {
+                match &self.content {
+                    FetchStatus::Complete(Content::Stories(story_list))
+                        if story_list.has_more() =>
+                    {
+                        let sorting = story_list.sorting;
+                        let page = story_list.page + 1;
+                        let story_ids = story_list.story_ids.clone();
+                        self.last_navigation =
+                            Some(Msg::FetchStoriesPage(sorting, page));
+                        self.push_route(&Route::Stories { sorting, page });
+                        self.start_loading();
+                        self.fetch_stories_page_with_ids(
+                            sorting, story_ids, page,
+                        )
+                    }
+                    _ => Cmd::none(),
+                }
+            }
+            Msg::ReorderStories(order) => {
+                self.story_order = order;
+                if let FetchStatus::Complete(Content::Stories(story_list)) =
+                    &mut self.content
+                {
+                    story_list.order = order;
+                }
+                Cmd::none()
+            }
+            Msg::OpenStory(story_id) => {
+                self.push_route(&Route::Story(story_id));
+                self.start_loading();
+                self.fetch_story_page(story_id)
+            }
+            Msg::SaveOffline(story_id) => match &self.content {
+                FetchStatus::Complete(Content::StoryPage(story_page))
+                    if story_page.id == story_id =>
+                {
+                    self.save_story_offline(story_page.clone())
+                }
+                _ => Cmd::none(),
+            },
+            Msg::SavedOffline(saved) => {
+                if let FetchStatus::Complete(Content::StoryPage(story_page)) =
+                    &mut self.content
+                {
+                    if story_page.id == saved.id {
+                        story_page.saved_at = saved.saved_at;
+                    }
+                }
+                Cmd::none()
+            }
+            Msg::ShowUserPage(username) => {
+                self.push_route(&Route::User(username.clone()));
+                self.start_loading();
+                log::trace!("showing user: {}", username);
+                self.fetch_user_page(username)
+            }
+            Msg::ShowCommentPermalink(comment_id) => {
+                self.push_route(&Route::Comment(comment_id));
+                self.start_loading();
+                log::trace!("showing comment: {}", comment_id);
+                self.fetch_comment_permalink(comment_id)
+            }
+            Msg::ShowRoute(route) => {
+                self.push_route(&route);
+                self.start_loading();
+                self.fetch_route(route)
+            }
+            Msg::ToggleShowDead => {
+                self.settings.show_dead = !self.settings.show_dead;
+                self.settings.save();
+                Cmd::none()
+            }
+            Msg::SearchInput(input) => {
+                self.search_input = input;
+                Cmd::none()
+            }
+            Msg::SubmitSearch => {
+                let query = self.search_input.trim();
+                if query.is_empty() {
+                    Cmd::none()
+                } else {
+                    let search = SearchQuery::new(query);
+                    self.update(Msg::Search(search))
+                }
+            }
+            Msg::Search(search) => {
+                self.push_route(&Route::Search(search.clone()));
+                self.start_loading();
+                self.fetch_search_results(search)
+            }
+            Msg::LoadMoreReplies(parent_id) => {
+                match &self.content {
+                    FetchStatus::Complete(content) => {
+                        let mut reply_ids = content.unloaded_replies(parent_id);
+                        reply_ids.truncate(api::REPLIES_PAGE_SIZE);
+                        self.fetch_replies(parent_id, reply_ids)
+                    }
+                    _ => Cmd::none(),
+                }
+            }
+            Msg::ReceivedReplies(parent_id, replies) => {
+                if let FetchStatus::Complete(content) = &mut self.content {
+                    if !content.insert_replies(parent_id, replies) {
+                        log::warn!("parent {} of the replies is no longer displayed", parent_id);
+                    }
+                }
+                Cmd::none()
+            }
+            Msg::RetryFailed => match &self.content {
+                FetchStatus::Complete(content) => {
+                    let item_ids = content
+                        .failed()
+                        .iter()
+                        .map(|failed| failed.id)
+                        .collect();
+                    self.fetch_failed(item_ids)
+                }
+                _ => Cmd::none(),
+            },
+            Msg::ReceivedRetried(fetched) => {
+                if let FetchStatus::Complete(content) = &mut self.content {
+                    content.merge_retried(fetched);
+                }
+                Cmd::none()
+            }
+            Msg::LoadNewComments(story_id) => {
+                match &self.content {
+                    FetchStatus::Complete(Content::StoryPage(story_page))
+                        if story_page.id == story_id =>
+                    {
+                        let new_kids = story_page.new_kids.clone();
+                        self.fetch_replies(story_id, new_kids)
+                    }
+                    _ => Cmd::none(),
+                }
+            }
+            Msg::PollUpdates => match &self.content {
+                FetchStatus::Complete(_) => self.fetch_updates(),
+                _ => Cmd::none(),
+            },
+            Msg::ReceivedUpdates(updates) => match &self.content {
+                FetchStatus::Complete(content) => {
+                    let (story_ids, comment_ids) = content.item_ids();
+                    let changed = |id: &i64| updates.items.contains(id);
+                    Cmd::batch(
+                        story_ids
+                            .into_iter()
+                            .filter(changed)
+                            .map(|story_id| self.fetch_story_update(story_id))
+                            .chain(
+                                comment_ids.into_iter().filter(changed).map(
+                                    |comment_id| {
+                                        self.fetch_comment_update(comment_id)
+                                    },
+                                ),
+                            )
+                            .collect::<Vec<_>>(),
+                    )
+                }
+                _ => Cmd::none(),
+            },
+            Msg::PatchStory(story) => {
+                if let FetchStatus::Complete(content) = &mut self.content {
+                    content.patch_story(story);
+                }
+                Cmd::none()
+            }
+            Msg::PatchComment(comment) => {
+                if let FetchStatus::Complete(content) = &mut self.content {
+                    content.patch_comment(comment);
+                }
+                Cmd::none()
+            }
+            Msg::ReceivedContent(mut content) => {
+                if let Content::Stories(story_list) = &mut content {
+                    story_list.order = self.story_order;
+                }
+                self.finish_loading(content);
+                self.offline = false;
+                Window::scroll_to_top(Msg::NoOp)
+            }
+            Msg::RevalidatedContent(mut content) => {
+                // the user may have navigated away while the content was refetched
+                if let FetchStatus::Complete(displayed) = &mut self.content {
+                    if displayed.is_same_page(&content) {
+                        if let Content::Stories(story_list) = &mut content {
+                            story_list.order = self.story_order;
+                        }
+                        *displayed = content;
+                        self.offline = false;
+                    }
+                }
+                Cmd::none()
+            }
+            Msg::ReceivedOfflineContent(content) => {
+                self.finish_loading(content);
+                self.offline = true;
+                Window::scroll_to_top(Msg::NoOp)
+            }
+            Msg::RequestError(server_error) => {
+                log::error!("Error: {}", server_error);
+                if self.pending {
+                    self.pending = false;
+                    self.toast = Some(server_error);
+                } else {
+                    self.content = FetchStatus::Error(server_error);
+                }
+                Cmd::none()
+            }
+            Msg::DismissToast => {
+                self.toast = None;
+                Cmd::none()
+            }
+            Msg::Retry => match self.last_navigation.clone() {
+                Some(msg) => self.update(msg),
+                None => self.update(Msg::FetchStories),
+            },
+            Msg::UrlChanged(url) => {
+                log::trace!("url changed to: {}", url);
+                let route = match self.router.route(&url) {
+                    Ok(route) => route,
+                    Err(e) => {
+                        log::warn!("{}", e);
+                        self.finish_loading(Content::from(self.router.unknown(&url)));
+                        return Cmd::none();
+                    }
+                };
+                // scrolled to the top once the content is received
+                self.start_loading();
+                self.fetch_route(route)
+            }
+            Msg::PoppedState(url, key) => {
+                self.leave_page();
+                let key = match key {
+                    Some(key) => key,
+                    // the entries added by following a hash link have no key yet
+                    None => self.replace_history_key(),
+                };
+                self.history.current = key;
+                match self.history.restore(key) {
+                    Some(entry) => {
+                        log::trace!("restoring {} at {}", url, entry.scroll_y);
+                        if let Content::SearchResults(results) = &entry.content {
+                            self.search_input = results.search.query.clone();
+                        }
+                        self.finish_loading(entry.content);
+                        self.offline = entry.offline;
+                        Self::restore_scroll(entry.scroll_y)
+                    }
+                    None => self.update_content(Msg::UrlChanged(url)),
+                }
+            }
+            Msg::Navigated(generation, msg) => {
+                if generation == self.navigation.generation {
+                    self.update(*msg)
+                } else {
+                    log::trace!("dropping {:?} of a superseded navigation", msg);
+                    Cmd::none()
+                }
+            }
+            Msg::NoOp => Cmd::none(),
+        }
+    }
+
+    /// wait for the content of a navigation, while the current content is still displayed
+    fn start_loading(&mut self) {
+        self.toast = None;
+        match self.content {
+            FetchStatus::Complete(_) => self.pending = true,
+            _ => self.content = FetchStatus::Loading,
+        }
+    }
+
+    /// display the content a navigation was waiting for
+    fn finish_loading(&mut self, content: Content) {
+        self.content = FetchStatus::Complete(content);
+        self.pending = false;
+        self.toast = None;
+    }
+
+    /// fetch the content of the current navigation, the fetch is cancelled
+    /// along with its requests when another navigation starts
+    fn fetch_content(&self, fetch: impl Future<Output = Msg> + 'static) -> Cmd<Msg> {
+        Cmd::new(self.navigation.cancellable(fetch))
+    }
+
+    fn fetch_route(&mut self, route: Route) -> Cmd<Msg> {
+        match route {
+            Route::Home => self.fetch_stories(),
+            Route::Stories { sorting, page } => self.fetch_stories_page(sorting, page),
+            Route::Story(story_id) => self.fetch_story_page(story_id),
+            Route::Comment(comment_id) => self.fetch_comment_permalink(comment_id),
+            Route::User(username) => self.fetch_user_page(username),
+            Route::Search(search) => {
+                self.search_input = search.query.clone();
+                self.fetch_search_results(search)
+            }
+        }
+    }
+
     fn fetch_stories(&self) -> Cmd<Msg> {
-        Cmd::new( async move{
-            match api::get_stories().await {
-                Ok(stories) => {
+        self.fetch_stories_with_sorting(StorySorting::default())
+    }
+
+    fn fetch_stories_with_sorting(
+        &self,
+        sorting: StorySorting,
+    ) -> Cmd<Msg> {
+        self.fetch_stories_page(sorting, 1)
+    }
+
+    /// a cached page is displayed right away, and refreshed in the background if it is stale
+    fn fetch_stories_page(&self, sorting: StorySorting, page: usize) -> Cmd<Msg> {
+        let client = self.client.clone();
+        if let Some(cached) = api::get_cached_stories_page(&client, sorting, page, api::STORIES_COUNT) {
+            return self.show_cached(cached.map(Content::from), || {
+                self.revalidate(async move {
+                    api::get_stories_page(&client, sorting, page, api::STORIES_COUNT)
+                        .await
+                        .map(Content::from)
+                })
+            });
+        }
+        self.fetch_content( async move{
+            match api::get_stories_page(&client, sorting, page, api::STORIES_COUNT).await {
+                Ok(story_list) => {
                     Msg::ReceivedContent( Content::from(
-                        stories,
+                        story_list,
                     ))
                 }
                 Err(e) => {
@@ -303,15 +908,18 @@ impl App{
         })
     }
 
-    fn fetch_stories_with_sorting(
+    fn fetch_stories_page_with_ids(
         &self,
         sorting: StorySorting,
+        story_ids: Vec<i64>,
+        page: usize,
     ) -> Cmd<Msg> {
-        Cmd::new( async move{
-            match api::get_stories_with_sorting(sorting).await {
-                Ok(stories) => {
+        let client = self.client.clone();
+        self.fetch_content( async move{
+            match api::get_stories_page_with_ids(&client, sorting, story_ids, page, api::STORIES_COUNT).await {
+                Ok(story_list) => {
                     Msg::ReceivedContent( Content::from(
-                        stories,
+                        story_list,
                     ))
                 }
                 Err(e) => {
@@ -322,16 +930,77 @@ impl App{
     }
 
 
+    /// a cached story is displayed right away and refreshed in the background if it is stale,
+    /// the copy saved for offline reading is shown when the story can't be fetched
     fn fetch_story_page(&self, story_id: i64) -> Cmd<Msg> {
-        Cmd::new( async move{
-            match api::get_story(story_id).await {
+        let client = self.client.clone();
+        if let Some(cached) = api::get_cached_story(&client, story_id) {
+            return self.show_cached(cached.map(Content::from), || {
+                self.revalidate(async move {
+                    api::get_story(&client, story_id).await.map(Content::from)
+                })
+            });
+        }
+        self.fetch_content( async move{
+            match api::get_story(&client, story_id).await {
                 Ok(story) => {
                     Msg::ReceivedContent( Content::from(
                         story,
                     ))
                 }
                 Err(e) => {
-                    Msg::RequestError(e)
+                    match api::get_offline_story(&client, story_id).await {
+                        Ok(Some(saved)) => {
+                            log::warn!("showing the offline copy of story {}: {}", story_id, e);
+                            Msg::ReceivedOfflineContent(Content::from(saved))
+                        }
+                        _ => Msg::RequestError(e),
+                    }
+                }
+            }
+        })
+    }
+
+    /// display the `cached` content, and `revalidate` it when it is stale
+    fn show_cached(
+        &self,
+        cached: Cached<Content>,
+        revalidate: impl FnOnce() -> Cmd<Msg>,
+    ) -> Cmd<Msg> {
+        let is_stale = cached.is_stale;
+        let received =
+            self.fetch_content(async move { Msg::ReceivedContent(cached.value) });
+        if is_stale {
+            Cmd::batch([received, revalidate()])
+        } else {
+            received
+        }
+    }
+
+    /// refetch the displayed content in the background, keeping it as is when that fails
+    fn revalidate(
+        &self,
+        fetch: impl Future<Output = Result<Content, ServerError>> + 'static,
+    ) -> Cmd<Msg> {
+        self.fetch_content( async move{
+            match fetch.await {
+                Ok(content) => Msg::RevalidatedContent(content),
+                Err(e) => {
+                    log::warn!("unable to refresh the content: {}", e);
+                    Msg::NoOp
+                }
+            }
+        })
+    }
+
+    fn save_story_offline(&self, story_page: StoryPageData) -> Cmd<Msg> {
+        let client = self.client.clone();
+        Cmd::new( async move{
+            match api::save_story_offline(&client, &story_page).await {
+                Ok(saved) => Msg::SavedOffline(saved),
+                Err(e) => {
+                    log::error!("unable to save story {} for offline reading: {}", story_page.id, e);
+                    Msg::NoOp
                 }
             }
         })
@@ -339,8 +1008,9 @@ impl App{
 
 
     fn fetch_comment_permalink(&self, comment_id: i64) -> Cmd<Msg> {
-        Cmd::new( async move{
-            match api::get_comment(comment_id).await {
+        let client = self.client.clone();
+        self.fetch_content( async move{
+            match api::get_comment(&client, comment_id).await {
                 Ok(comment) => {
                     Msg::ReceivedContent( Content::from(
                         comment,
@@ -354,9 +1024,98 @@ impl App{
     }
 
 
-    fn fetch_user_page(&self, username: String) -> Cmd<Msg> {
+    fn fetch_replies(&self, parent_id: i64, reply_ids: Vec<i64>) -> Cmd<Msg> {
+        if reply_ids.is_empty() {
+            return Cmd::none();
+        }
+        let client = self.client.clone();
+        Cmd::new( async move{
+            // the replies that failed stay in the count of replies still to load
+            let replies = api::get_comments(&client, &reply_ids).await;
+            Msg::ReceivedReplies(parent_id, replies.items)
+        })
+    }
+
+    fn fetch_failed(&self, item_ids: Vec<i64>) -> Cmd<Msg> {
+        if item_ids.is_empty() {
+            return Cmd::none();
+        }
+        let client = self.client.clone();
+        Cmd::new( async move{
+            let fetched = api::get_items(&client, &item_ids).await;
+            Msg::ReceivedRetried(fetched)
+        })
+    }
+
+    fn fetch_search_results(&self, search: SearchQuery) -> Cmd<Msg> {
+        let client = self.client.clone();
+        self.fetch_content( async move{
+            match api::get_search_results(&client, &search).await {
+                Ok(results) => {
+                    Msg::ReceivedContent( Content::from(
+                        results,
+                    ))
+                }
+                Err(e) => {
+                    Msg::RequestError(e)
+                }
+            }
+        })
+    }
+
+    // The live updates are best effort, failures are logged and
+    // the displayed content is kept as is.
+
+    fn fetch_updates(&self) -> Cmd<Msg> {
+        let client = self.client.clone();
+        Cmd::new( async move{
+            match api::get_updates(&client).await {
+                Ok(updates) => Msg::ReceivedUpdates(updates),
+                Err(e) => {
+                    log::warn!("unable to fetch updates: {}", e);
+                    Msg::NoOp
+                }
+            }
+        })
+    }
+
+    fn fetch_story_update(&self, story_id: i64) -> Cmd<Msg> {
+        let client = self.client.clone();
         Cmd::new( async move{
-            match api::get_user_page(&username).await {
+            match api::get_story_update(&client, story_id).await {
+                Ok(story) => Msg::PatchStory(story),
+                Err(e) => {
+                    log::warn!("unable to update story {}: {}", story_id, e);
+                    Msg::NoOp
+                }
+            }
+        })
+    }
+
+    fn fetch_comment_update(&self, comment_id: i64) -> Cmd<Msg> {
+        let client = self.client.clone();
+        Cmd::new( async move{
+            match api::get_comment_update(&client, comment_id).await {
+                Ok(comment) => Msg::PatchComment(comment),
+                Err(e) => {
+                    log::warn!("unable to update comment {}: {}", comment_id, e);
+                    Msg::NoOp
+                }
+            }
+        })
+    }
+
+    fn fetch_user_page(&self, username: String) -> Cmd<Msg> {
+        let client = self.client.clone();
+        if let Some(cached) = api::get_cached_user_page(&client, &username) {
+            return self.show_cached(cached.map(Content::from), || {
+                self.revalidate(async move {
+                    api::get_user_page(&client, &username).await.map(Content::from)
+                })
+            });
+        }
+        self.fetch_content( async move{
+            match api::get_user_page(&client, &username).await {
                 Ok(user_page) => {
                     Msg::ReceivedContent( Content::from(
                         user_page,
@@ -369,20 +1128,104 @@ impl App{
         })
     }
 
-    fn push_state_url(url: &str) {
+    /// add a history entry for `route`, unless it is the current one.
+    /// Unlike setting the hash, this doesn't trigger a popstate, so the
+    /// content of the route is not fetched a second time.
+    fn push_route(&mut self, route: &Route) {
         let window = sauron::window();
-        let location = window.location();
-        log::trace!("setting hash to: {}", url);
-        
-        // For hash routing, set the hash directly
-        if url.starts_with('#') {
-            location.set_hash(url).expect("must set hash");
-        } else if url == "/" {
-            location.set_hash("").expect("must set hash");
-        } else {
-            location.set_hash(&format!("#{}", url.trim_start_matches('/'))).expect("must set hash");
+        if self.router.route(&current_location()).as_ref() == Ok(route) {
+            return;
+        }
+        self.leave_page();
+        let key = self.history.next_key();
+        let url = self.router.href(route);
+        log::trace!("pushing url: {}", url);
+        let pushed = window.history().and_then(|history| {
+            history.push_state_with_url(&JsValue::from_f64(key as f64), "", Some(&url))
+        });
+        match pushed {
+            Ok(()) => self.history.current = key,
+            Err(e) => log::error!("unable to push {}: {:?}", url, e),
         }
     }
-}
 
+    /// keep the displayed page along with where it is scrolled to,
+    /// so it is restored when its history entry is gone back to.
+    /// A page kept while the next one is pending is not the page of the current entry
+    fn leave_page(&mut self) {
+        if self.pending {
+            return;
+        }
+        if let FetchStatus::Complete(content) = &self.content {
+            self.history.leave(history::Entry {
+                content: content.clone(),
+                offline: self.offline,
+                scroll_y: sauron::window().scroll_y().unwrap_or_default(),
+            });
+        }
+    }
+
+    /// key the current history entry, and restore the scroll positions of
+    /// the pages in place of the browser, which would do it before they are rendered
+    fn init_history(&mut self) {
+        let history = match sauron::window().history() {
+            Ok(history) => history,
+            Err(e) => {
+                log::error!("unable to access the history: {:?}", e);
+                return;
+            }
+        };
+        let scroll_restoration = js_sys::Reflect::set(
+            &history,
+            &JsValue::from_str("scrollRestoration"),
+            &JsValue::from_str("manual"),
+        );
+        if let Err(e) = scroll_restoration {
+            log::warn!("unable to restore the scroll positions manually: {:?}", e);
+        }
+        self.history.current = match history.state().ok().and_then(|state| state.as_f64()) {
+            Some(key) => key as u64,
+            None => self.replace_history_key(),
+        };
+    }
+
+    /// give the current history entry a new key
+    fn replace_history_key(&self) -> u64 {
+        let key = self.history.next_key();
+        let replaced = sauron::window()
+            .history()
+            .and_then(|history| history.replace_state(&JsValue::from_f64(key as f64), ""));
+        if let Err(e) = replaced {
+            log::error!("unable to key the history entry: {:?}", e);
+        }
+        key
+    }
+
+    /// scroll to `scroll_y` once the restored page is rendered, which may take a few frames
+    fn restore_scroll(scroll_y: f64) -> Cmd<Msg> {
+        Cmd::new(async move {
+            for _ in 0..RESTORE_SCROLL_ATTEMPTS {
+                sauron::dom::delay(RESTORE_SCROLL_INTERVAL_MS).await;
+                let window = sauron::window();
+                window.scroll_to_with_x_and_y(0.0, scroll_y);
+                // the page may not be tall enough yet to be scrolled that far
+                if window.scroll_y().unwrap_or_default() >= scroll_y - 1.0 {
+                    break;
+                }
+            }
+            Msg::NoOp
+        })
+    }
+}
 
+/// the path, query and hash of the url of the page, which is what is routed
+#[cfg(feature = "wasm")]
+fn current_location() -> String {
+    let location = sauron::window().location();
+    format!(
+        "{}{}{}",
+        location.pathname().unwrap_or_default(),
+        location.search().unwrap_or_default(),
+        location.hash().unwrap_or_default(),
+    )
+}
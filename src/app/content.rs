@@ -1,5 +1,21 @@
-use crate::app;
-use crate::types::{Comment, StoryItem, StoryPageData, UserData};
+use crate::api::{
+    Fetched,
+    SearchHit,
+    SearchResults,
+};
+use crate::app::{
+    self,
+    Settings,
+};
+use crate::route::{
+    Route,
+    Router,
+    UnknownRoute,
+};
+use crate::types::{
+    Comment, FailedFetch, Item, PollOption, StoryItem, StoryList,
+    StoryPageData, UserData,
+};
 use sauron::prelude::*;
 use serde::{Deserialize, Serialize};
 //use sauron::safe_html;
@@ -8,71 +24,376 @@ use serde::{Deserialize, Serialize};
     Debug, Deserialize, Serialize, PartialEq, Clone, derive_more::From,
 )]
 pub enum Content {
-    Stories(Vec<StoryItem>),
+    Stories(StoryList),
     StoryPage(StoryPageData),
     CommentPermalink(Comment),
     UserPage(UserData),
+    SearchResults(SearchResults),
+    /// the url is not a page of the app
+    NotFound(UnknownRoute),
 }
 
 impl Content {
-    pub fn view(&self) -> Node<app::Msg> {
+    /// the ids of the replies to `parent_id` which are not loaded yet,
+    /// where the parent can be the story or any of the displayed comments
+    pub fn unloaded_replies(&self, parent_id: i64) -> Vec<i64> {
         match self {
-            Content::Stories(stories) => {
+            Content::StoryPage(story_page) if story_page.id == parent_id => {
+                story_page.unloaded_kids()
+            }
+            Content::StoryPage(story_page) => story_page
+                .find_comment(parent_id)
+                .map(|comment| comment.unloaded_kids())
+                .unwrap_or_default(),
+            Content::CommentPermalink(comment) => comment
+                .find(parent_id)
+                .map(|comment| comment.unloaded_kids())
+                .unwrap_or_default(),
+            Content::UserPage(user_data) => user_data
+                .comments
+                .iter()
+                .find_map(|comment| comment.find(parent_id))
+                .map(|comment| comment.unloaded_kids())
+                .unwrap_or_default(),
+            _ => vec![],
+        }
+    }
+
+    /// splice `replies` into the displayed comment tree under `parent_id`
+    pub fn insert_replies(&mut self, parent_id: i64, replies: Vec<Comment>) -> bool {
+        match self {
+            Content::StoryPage(story_page) => {
+                story_page.insert_replies(parent_id, replies)
+            }
+            Content::CommentPermalink(comment) => {
+                comment.insert_replies(parent_id, replies)
+            }
+            Content::UserPage(user_data) => user_data
+                .comments
+                .iter_mut()
+                .find(|comment| comment.find(parent_id).is_some())
+                .is_some_and(|comment| comment.insert_replies(parent_id, replies)),
+            _ => false,
+        }
+    }
+
+    /// the items of this page which could not be fetched
+    pub fn failed(&self) -> &[FailedFetch] {
+        match self {
+            Content::Stories(story_list) => &story_list.failed,
+            Content::StoryPage(story_page) => &story_page.failed,
+            Content::UserPage(user_data) => &user_data.failed,
+            _ => &[],
+        }
+    }
+
+    /// put the items which were fetched again in place of the ones that failed,
+    /// the items which failed again are kept as failed
+    pub fn merge_retried(&mut self, fetched: Fetched<Item>) {
+        let failed = match self {
+            Content::Stories(story_list) => &mut story_list.failed,
+            Content::StoryPage(story_page) => &mut story_page.failed,
+            Content::UserPage(user_data) => &mut user_data.failed,
+            _ => return,
+        };
+        // the items of another page may arrive if the user navigated in the meantime
+        let was_failed = failed.iter().map(|failed| failed.id).collect::<Vec<_>>();
+        let items = fetched
+            .items
+            .into_iter()
+            .filter(|item| was_failed.contains(&item.id()))
+            .collect::<Vec<_>>();
+        let failed_again = fetched.failed;
+        failed.retain(|failed| {
+            !items.iter().any(|item| item.id() == failed.id)
+                && !failed_again.iter().any(|again| again.id == failed.id)
+        });
+        failed.extend(
+            failed_again
+                .into_iter()
+                .filter(|again| was_failed.contains(&again.id)),
+        );
+
+        match self {
+            Content::Stories(story_list) => {
+                story_list.merge_stories(
+                    items.into_iter().filter_map(Item::into_story).collect(),
+                );
+            }
+            Content::StoryPage(story_page) => {
+                let (poll_options, comments): (Vec<_>, Vec<_>) = items
+                    .into_iter()
+                    .partition(|item| story_page.parts.contains(&item.id()));
+                story_page.merge_poll_options(
+                    poll_options
+                        .into_iter()
+                        .filter_map(Item::into_poll_option)
+                        .collect(),
+                );
+                // the failed comments may be replies at any level of the comment tree
+                for comment in comments.into_iter().filter_map(Item::into_comment) {
+                    let parent_id = story_page.parent_of(comment.id).unwrap_or(story_page.id);
+                    story_page.insert_replies(parent_id, vec![comment]);
+                }
+            }
+            Content::UserPage(user_data) => {
+                let (stories, comments): (Vec<_>, Vec<_>) =
+                    items.into_iter().partition(Item::is_story);
+                user_data.merge_submissions(
+                    stories.into_iter().filter_map(Item::into_story).collect(),
+                    comments.into_iter().filter_map(Item::into_comment).collect(),
+                );
+            }
+            _ => (),
+        }
+    }
+
+    /// whether `other` is a copy of this page, fresh or not
+    pub fn is_same_page(&self, other: &Content) -> bool {
+        match (self, other) {
+            (Content::Stories(list), Content::Stories(other)) => {
+                list.sorting == other.sorting && list.page == other.page
+            }
+            (Content::StoryPage(story), Content::StoryPage(other)) => story.id == other.id,
+            (Content::CommentPermalink(comment), Content::CommentPermalink(other)) => {
+                comment.id == other.id
+            }
+            (Content::UserPage(user), Content::UserPage(other)) => user.id == other.id,
+            (Content::SearchResults(results), Content::SearchResults(other)) => {
+                results.search == other.search
+            }
+            _ => false,
+        }
+    }
+
+    /// the ids of the displayed stories and the ids of the displayed comments
+    pub fn item_ids(&self) -> (Vec<i64>, Vec<i64>) {
+        match self {
+            Content::Stories(story_list) => (
+                story_list.stories.iter().map(|story| story.id).collect(),
+                vec![],
+            ),
+            Content::StoryPage(story_page) => {
+                (vec![story_page.id], story_page.comment_ids())
+            }
+            Content::CommentPermalink(comment) => (vec![], comment.ids()),
+            Content::UserPage(user_data) => (
+                user_data.stories.iter().map(|story| story.id).collect(),
+                user_data.comments.iter().flat_map(|comment| comment.ids()).collect(),
+            ),
+            Content::SearchResults(_) | Content::NotFound(_) => (vec![], vec![]),
+        }
+    }
+
+    /// update the displayed story with its latest state
+    pub fn patch_story(&mut self, story: StoryItem) {
+        match self {
+            Content::Stories(StoryList { stories, .. })
+            | Content::UserPage(UserData { stories, .. }) => {
+                if let Some(displayed) =
+                    stories.iter_mut().find(|displayed| displayed.id == story.id)
+                {
+                    *displayed = story;
+                }
+            }
+            Content::StoryPage(story_page) if story_page.id == story.id => {
+                story_page.patch(&story);
+            }
+            _ => (),
+        }
+    }
+
+    /// update the displayed comment with its latest state
+    pub fn patch_comment(&mut self, comment: Comment) {
+        let displayed = match self {
+            Content::StoryPage(story_page) => {
+                story_page.find_comment_mut(comment.id)
+            }
+            Content::CommentPermalink(root) => root.find_mut(comment.id),
+            Content::UserPage(user_data) => user_data
+                .comments
+                .iter_mut()
+                .find_map(|root| root.find_mut(comment.id)),
+            _ => None,
+        };
+        if let Some(displayed) = displayed {
+            displayed.patch(&comment);
+        }
+    }
+
+    pub fn view(&self, settings: &Settings, router: &Router) -> Node<app::Msg> {
+        match self {
+            Content::Stories(story_list) => {
                 node! {
                     <div class="index-page">
-                       {self.view_story_preview_list(stories)}
+                       {self.view_failed()}
+                       {self.view_story_preview_list(story_list.ranked_stories(), self.view_more_link(story_list, router), router)}
                     </div>
                 }
             }
             Content::StoryPage(story_page) => {
                 node! {
                     <div class="story-page">
-                        { self.view_story_page(story_page) }
+                        { self.view_failed() }
+                        { self.view_story_page(story_page, settings, router) }
                     </div>
                 }
             }
             Content::UserPage(user_data) => {
                 node! {
                     <div class="user-details">
+                        { self.view_failed() }
                         <h4>{ text!("{}:",user_data.id) }</h4>
                         <div>{ for node in crate::util::parse_html_to_nodes(&user_data.about) { node } }</div>
                         <span>{ text!("{} karma", user_data.karma) }</span>
                         <div class="submissions">
-                             {self.view_story_preview_list(&user_data.stories)}
+                             {self.view_story_preview_list(user_data.stories.iter().enumerate().map(|(i, story)| (i + 1, story)).collect(), None, router)}
                         </div>
+                        {
+                            if user_data.comments.is_empty() {
+                                text("")
+                            } else {
+                                node! {
+                                    <div class="user-comments">
+                                        <h4>"comments:"</h4>
+                                        <ul>
+                                        {
+                                            for comment in &user_data.comments {
+                                                self.view_comment(comment, settings, router)
+                                            }
+                                        }
+                                        </ul>
+                                    </div>
+                                }
+                            }
+                        }
                     </div>
                 }
             }
             Content::CommentPermalink(comment) => {
                 node! {
                     <div class="comment-permalink">
-                        {self.view_comment(comment)}
+                        {self.view_comment(comment, settings, router)}
                     </div>
                 }
             }
+            Content::SearchResults(results) => {
+                node! {
+                    <div class="search-page">
+                        {self.view_search_results(results, router)}
+                    </div>
+                }
+            }
+            Content::NotFound(unknown) => self.view_not_found(unknown, router),
         }
     }
 
-    fn view_story_preview_list(&self, stories: &[StoryItem]) -> Node<app::Msg> {
+    /// the page of a url which is not a route, linking to the page it was likely meant to be
+    fn view_not_found(&self, unknown: &UnknownRoute, router: &Router) -> Node<app::Msg> {
+        node! {
+            <article class="error not-found">
+                <h2>"No such page"</h2>
+                <p>{text!("There is no page at {}.", unknown.url)}</p>
+                {
+                    if let Some(suggestion) = unknown.suggestion.clone() {
+                        let url = router.href(&suggestion);
+                        node! {
+                            <p class="suggestion">
+                                "Did you mean "
+                                <a href=&url
+                                    on_click=move|e|{
+                                        e.prevent_default();
+                                        app::Msg::ShowRoute(suggestion.clone())
+                                    }>{text(&url)}</a>
+                                "?"
+                            </p>
+                        }
+                    } else {
+                        text("")
+                    }
+                }
+                <a href=router.href(&Route::Home)
+                    on_click=|e|{
+                        e.prevent_default();
+                        app::Msg::FetchStories
+                    }>"Back to the front page"</a>
+            </article>
+        }
+    }
+
+    /// a banner telling how many items of the page failed to load, with a link to retry them.
+    /// The replies which failed at any level of the comment tree of a story are counted
+    fn view_failed(&self) -> Node<app::Msg> {
+        let failed = self.failed();
+        if failed.is_empty() {
+            return text("");
+        }
+        let noun = if failed.len() == 1 { "item" } else { "items" };
+        node! {
+            <div class="failed-fetches">
+                { text!("{} {} failed to load — ", failed.len(), noun) }
+                <a href="#"
+                    on_click=|e|{
+                        e.prevent_default();
+                        app::Msg::RetryFailed
+                    }>
+                    "retry"
+                </a>
+            </div>
+        }
+    }
+
+    /// the stories are paired with their item number, so the numbering continues across pages
+    fn view_story_preview_list(
+        &self,
+        stories: Vec<(usize, &StoryItem)>,
+        more: Option<Node<app::Msg>>,
+        router: &Router,
+    ) -> Node<app::Msg> {
         node! {
             <ol>
             {
-                for (i, story_preview) in stories.iter().enumerate() {
+                for (number, story_preview) in stories {
                     node! {
                         <li>
-                            <div class="item-number">{text!("{}. ",i+1)}</div>
+                            <div class="item-number">{text!("{}. ",number)}</div>
                             <div class="preview-wrapper">
-                                {self.view_story_preview(story_preview)}
+                                {self.view_story_preview(story_preview, router)}
                             </div>
                         </li>
                     }
                 }
             }
+            {
+                if let Some(more) = more {
+                    node! {
+                        <li class="more">{more}</li>
+                    }
+                } else {
+                    text("")
+                }
+            }
             </ol>
         }
     }
 
-    fn view_story_preview(&self, story_preview: &StoryItem) -> Node<app::Msg> {
+    fn view_more_link(&self, story_list: &StoryList, router: &Router) -> Option<Node<app::Msg>> {
+        if story_list.has_more() {
+            Some(node! {
+                <a href=router.href(&Route::Stories { sorting: story_list.sorting, page: story_list.page + 1 })
+                    on_click=|e|{
+                        e.prevent_default();
+                        app::Msg::LoadMoreStories
+                    }>
+                    "More"
+                </a>
+            })
+        } else {
+            None
+        }
+    }
+
+    fn view_story_preview(&self, story_preview: &StoryItem, router: &Router) -> Node<app::Msg> {
         // we copy story_preview_id here because it will be moved into the `on_click` event
         // listener in the links to the comments.
         //
@@ -101,7 +422,7 @@ impl Content {
                     </h2>
                     <span class="story-details">
                         {  text!("{} points | ",story_preview.score) }
-                        <a href=format!("/user/{}", story_preview.by)
+                        <a href=router.href(&Route::User(story_preview.by.clone()))
                             on_click=move|e|{
                                 e.prevent_default();
                                 app::Msg::ShowUserPage(story_preview_by.clone())
@@ -109,7 +430,7 @@ impl Content {
                             { text!(" by {}",story_preview.by) }
                         </a>
                         <span title="time">{ text!(" | {} ago |", crate::util::time_ago(story_preview.time)) }</span>
-                        <a href=format!("/item/{}", story_preview.id)
+                        <a href=router.href(&Route::Story(story_preview.id))
                             on_click=move|e|{
                                 e.prevent_default();
                                 app::Msg::OpenStory(story_preview_id)
@@ -122,53 +443,279 @@ impl Content {
         }
     }
 
-    fn view_story_page(&self, story_page: &StoryPageData) -> Node<app::Msg> {
+    fn view_search_results(&self, results: &SearchResults, router: &Router) -> Node<app::Msg> {
+        let next_search = results.search.next_page();
         node! {
             <div>
-                { self.view_story_preview(&story_page.preview()) }
+                <p class="search-summary">{text!("{} results for \"{}\"", results.nb_hits, results.search.query)}</p>
+                <ol>
+                {
+                    for (i, hit) in results.hits.iter().enumerate() {
+                        node! {
+                            <li>
+                                <div class="item-number">{text!("{}. ", results.offset() + i + 1)}</div>
+                                <div class="preview-wrapper">
+                                    {self.view_search_hit(hit, router)}
+                                </div>
+                            </li>
+                        }
+                    }
+                }
+                {
+                    if results.has_more() {
+                        node! {
+                            <li class="more">
+                                <a href=router.href(&Route::Search(next_search.clone()))
+                                    on_click=move|e|{
+                                        e.prevent_default();
+                                        app::Msg::Search(next_search.clone())
+                                    }>
+                                    "More"
+                                </a>
+                            </li>
+                        }
+                    } else {
+                        text("")
+                    }
+                }
+                </ol>
+            </div>
+        }
+    }
+
+    fn view_search_hit(&self, hit: &SearchHit, router: &Router) -> Node<app::Msg> {
+        let author = hit.author.clone();
+        // comments link to the story they are posted in
+        let story_id = if hit.is_comment() { hit.story_id } else { hit.id() };
+        let title = hit
+            .title
+            .as_ref()
+            .or(hit.story_title.as_ref())
+            .cloned()
+            .unwrap_or_default();
+        node! {
+            <div class="search-hit">
+                {
+                    if hit.is_comment() {
+                        node! {
+                            <div class="comment">
+                                { for node in crate::util::parse_html_to_nodes(hit.comment_text.as_deref().unwrap_or_default()) { node } }
+                            </div>
+                        }
+                    } else if let Some(url) = &hit.url {
+                        node! {
+                            <h2><a href=url target="_blank" rel="noopener noreferrer">{text(&title)}</a></h2>
+                        }
+                    } else {
+                        node! { <h2>{text(&title)}</h2> }
+                    }
+                }
+                <span class="story-details">
+                    {
+                        if let Some(points) = hit.points {
+                            text!("{} points | ", points)
+                        } else {
+                            text("")
+                        }
+                    }
+                    <a href=router.href(&Route::User(hit.author.clone()))
+                        on_click=move|e|{
+                            e.prevent_default();
+                            app::Msg::ShowUserPage(author.clone())
+                        }>
+                        { text!(" by {}", hit.author) }
+                    </a>
+                    <span title="time">{ text!(" | {} ago | ", crate::util::time_ago(hit.time())) }</span>
+                    {
+                        if let Some(story_id) = story_id {
+                            let label = if hit.is_comment() {
+                                format!("on: {}", title)
+                            } else {
+                                format!("{} comments", hit.num_comments.unwrap_or(0))
+                            };
+                            node! {
+                                <a href=router.href(&Route::Story(story_id))
+                                    on_click=move|e|{
+                                        e.prevent_default();
+                                        app::Msg::OpenStory(story_id)
+                                    }>
+                                    { text(label) }
+                                </a>
+                            }
+                        } else {
+                            text("")
+                        }
+                    }
+                </span>
+            </div>
+        }
+    }
+
+    fn view_story_page(&self, story_page: &StoryPageData, settings: &Settings, router: &Router) -> Node<app::Msg> {
+        node! {
+            <div>
+                { self.view_story_preview(&story_page.preview(), router) }
+                { self.view_save_offline(story_page) }
+                { self.view_poll_options(&story_page.poll_options) }
+                { self.view_new_comments(story_page, router) }
                 <ul class="comment-component">
                 {
                     for comment in story_page.comments.iter(){
-                        self.view_comment(comment)
+                        self.view_comment(comment, settings, router)
                     }
                 }
                 </ul>
+                { self.view_more_replies(story_page.id, Route::Story(story_page.id), story_page.unloaded_kids().len(), "comment", router) }
             </div>
         }
     }
 
-    fn view_comment(&self, comment: &Comment) -> Node<app::Msg> {
+    fn view_save_offline(&self, story_page: &StoryPageData) -> Node<app::Msg> {
+        let story_id = story_page.id;
+        let label = match story_page.saved_at {
+            Some(saved_at) => format!(
+                "saved for offline reading {} ago, save again",
+                crate::util::time_ago(saved_at)
+            ),
+            None => String::from("save for offline reading"),
+        };
+        node! {
+            <div class="save-offline">
+                <a href="#"
+                    on_click=move|e|{
+                        e.prevent_default();
+                        app::Msg::SaveOffline(story_id)
+                    }>{text(label)}
+                </a>
+            </div>
+        }
+    }
+
+    /// deleted comments, and dead ones unless showdead is set, are shown as a placeholder
+    /// so their replies stay in place, they are left out when they have no replies
+    fn view_comment(&self, comment: &Comment, settings: &Settings, router: &Router) -> Node<app::Msg> {
+        if comment.is_empty(settings.show_dead) {
+            return text("");
+        }
+        node! {
+            <li class="comment-item">
+                {
+                    if comment.deleted {
+                        node! { <div class="comment-details placeholder">"[deleted]"</div> }
+                    } else if comment.is_hidden(settings.show_dead) {
+                        node! { <div class="comment-details placeholder">"[dead]"</div> }
+                    } else {
+                        self.view_comment_content(comment, router)
+                    }
+                }
+                <ul class="sub-comments">
+                {
+                    for sub in comment.sub_comments.iter().filter(|sub| !sub.is_empty(settings.show_dead)) {
+                        node!{
+                            <li>
+                                {self.view_comment(sub, settings, router)}
+                            </li>
+                        }
+                    }
+                }
+                </ul>
+                { self.view_more_replies(comment.id, Route::Comment(comment.id), comment.unloaded_kids().len(), "reply", router) }
+            </li>
+        }
+    }
+
+    fn view_comment_content(&self, comment: &Comment, router: &Router) -> Node<app::Msg> {
         let comment_id = comment.id;
         let comment_by = comment.by.clone();
         node! {
-            <li class="comment-item">
+            <div class=if comment.dead { "comment-content dead" } else { "comment-content" }>
                 <div class="comment-details">
-                    <a href=format!("/user/{}",comment.by)
+                    <a href=router.href(&Route::User(comment.by.clone()))
                         on_click=move|e|{
                             e.prevent_default();
                             app::Msg::ShowUserPage(comment_by.clone())
                         }>{text(&comment.by)}
                     </a>
-                    <a href=format!("/comment/{}",comment.id)
+                    <a href=router.href(&Route::Comment(comment.id))
                         on_click=move|e|{
                             e.prevent_default();
                             app::Msg::ShowCommentPermalink(comment_id)
                         }>{text!(" {} ago", crate::util::time_ago(comment.time))}
                     </a>
+                    { if comment.dead { text(" [dead]") } else { text("") } }
                 </div>
                 <div class="comment">{ for node in crate::util::parse_html_to_nodes(&comment.text) { node } }</div>
-                <ul class="sub-comments">
-                {
-                    for sub in &comment.sub_comments{
-                        node!{
-                            <li>
-                                {self.view_comment(sub)}
-                            </li>
-                        }
+            </div>
+        }
+    }
+
+    /// the options of a poll with their votes, drawn as bars relative to the total votes
+    fn view_poll_options(&self, poll_options: &[PollOption]) -> Node<app::Msg> {
+        if poll_options.is_empty() {
+            return text("");
+        }
+        let total_votes: i64 = poll_options.iter().map(|option| option.score.max(0)).sum();
+        node! {
+            <ul class="poll-options">
+            {
+                for option in poll_options {
+                    let percent = if total_votes > 0 {
+                        option.score.max(0) as f64 * 100.0 / total_votes as f64
+                    } else {
+                        0.0
+                    };
+                    node! {
+                        <li class="poll-option">
+                            <div class="poll-option-text">{ for node in crate::util::parse_html_to_nodes(&option.text) { node } }</div>
+                            <div class="poll-option-bar">
+                                <div class="poll-option-fill" style=format!("width: {:.1}%", percent)></div>
+                            </div>
+                            <span class="story-details">{ text!("{} points", option.score) }</span>
+                        </li>
                     }
                 }
-                </ul>
-            </li>
+            }
+            </ul>
+        }
+    }
+
+    /// an indicator of the comments posted since the page was loaded, which loads them when clicked
+    fn view_new_comments(&self, story_page: &StoryPageData, router: &Router) -> Node<app::Msg> {
+        let count = story_page.new_kids.len();
+        if count == 0 {
+            return text("");
+        }
+        let story_id = story_page.id;
+        node! {
+            <a class="new-comments" href=router.href(&Route::Story(story_id))
+                on_click=move|e|{
+                    e.prevent_default();
+                    app::Msg::LoadNewComments(story_id)
+                }>
+                { if count == 1 { text("1 new comment") } else { text!("{} new comments", count) } }
+            </a>
+        }
+    }
+
+    /// a link to load the `count` replies of `parent_id` which are not loaded yet,
+    /// which opens the page of the parent at `parent_route` when it is opened in a new tab
+    fn view_more_replies(&self, parent_id: i64, parent_route: Route, count: usize, noun: &str, router: &Router) -> Node<app::Msg> {
+        if count == 0 {
+            return text("");
+        }
+        let label = match (count, noun) {
+            (1, _) => format!("1 more {}", noun),
+            (_, "reply") => format!("{} more replies", count),
+            _ => format!("{} more {}s", count, noun),
+        };
+        node! {
+            <a class="more-replies" href=router.href(&parent_route)
+                on_click=move|e|{
+                    e.prevent_default();
+                    app::Msg::LoadMoreReplies(parent_id)
+                }>
+                {text(label)}
+            </a>
         }
     }
 }
@@ -0,0 +1,51 @@
+//! Keeps the pages left through the history along with where they were scrolled to,
+//! so going back to a page shows it as it was left instead of fetching it again.
+use super::Content;
+use std::collections::VecDeque;
+
+/// how many of the pages left are kept, the older ones are fetched again when going back to them
+const MAX_ENTRIES: usize = 20;
+
+/// A page which was left, as it was displayed
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub content: Content,
+    pub offline: bool,
+    /// how far down the window was scrolled
+    pub scroll_y: f64,
+}
+
+/// The pages left, keyed by the history entry they were displayed in.
+/// The key of an entry is kept in its `history.state`
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    /// the key of the history entry which is displayed
+    pub current: u64,
+    /// the most recently left last
+    entries: VecDeque<(u64, Entry)>,
+}
+
+impl History {
+    /// a key for a new history entry, the keys of the entries
+    /// from before a reload of the page are not reused
+    pub fn next_key(&self) -> u64 {
+        let now = chrono::Utc::now().timestamp_millis().max(0) as u64;
+        now.max(self.current + 1)
+    }
+
+    /// keep `entry` as the page left from the current history entry
+    pub fn leave(&mut self, entry: Entry) {
+        let current = self.current;
+        self.entries.retain(|(key, _)| *key != current);
+        self.entries.push_back((current, entry));
+        if self.entries.len() > MAX_ENTRIES {
+            self.entries.pop_front();
+        }
+    }
+
+    /// take the page which was left from the history entry of `key`
+    pub fn restore(&mut self, key: u64) -> Option<Entry> {
+        let position = self.entries.iter().position(|(k, _)| *k == key)?;
+        self.entries.remove(position).map(|(_, entry)| entry)
+    }
+}
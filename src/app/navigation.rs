@@ -0,0 +1,68 @@
+//! Keeps track of the navigation in progress, so the content fetched for the
+//! navigations that were superseded by another one is not displayed.
+use super::Msg;
+use futures::{
+    channel::oneshot,
+    future::{
+        self,
+        Either,
+        FutureExt,
+        Shared,
+    },
+};
+use std::{
+    future::Future,
+    sync::Arc,
+};
+
+/// The navigation in progress, each navigation has the generation after the one before it
+#[derive(Clone, Default)]
+pub struct Navigation {
+    pub generation: u64,
+    /// dropped when the navigation is superseded, which resolves `cancelled`
+    superseded: Option<Arc<oneshot::Sender<()>>>,
+    cancelled: Option<Shared<oneshot::Receiver<()>>>,
+}
+
+impl Navigation {
+    /// supersede the current navigation, cancelling its fetches,
+    /// and return the generation of the new one
+    pub fn start(&mut self) -> u64 {
+        let (superseded, cancelled) = oneshot::channel();
+        self.generation += 1;
+        self.superseded = Some(Arc::new(superseded));
+        self.cancelled = Some(cancelled.shared());
+        self.generation
+    }
+
+    /// `fetch` as part of this navigation, it is dropped along with its requests
+    /// as soon as the navigation is superseded
+    pub fn cancellable(
+        &self,
+        fetch: impl Future<Output = Msg> + 'static,
+    ) -> impl Future<Output = Msg> + 'static {
+        let cancelled = self.cancelled.clone();
+        let generation = self.generation;
+        async move {
+            let cancelled = match cancelled {
+                Some(cancelled) => cancelled,
+                None => return fetch.await,
+            };
+            match future::select(Box::pin(fetch), cancelled).await {
+                Either::Left((msg, _)) => msg,
+                Either::Right(_) => {
+                    log::trace!("navigation {} was superseded", generation);
+                    Msg::NoOp
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Debug for Navigation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("Navigation")
+            .field("generation", &self.generation)
+            .finish_non_exhaustive()
+    }
+}
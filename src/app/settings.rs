@@ -0,0 +1,48 @@
+//! Display preferences of the reader, kept in the local storage of the browser.
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// the local storage key the settings are saved under
+#[cfg(feature = "wasm")]
+const STORAGE_KEY: &str = "hn-settings";
+
+/// How the content is displayed
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    /// show the content of the dead comments instead of a placeholder
+    pub show_dead: bool,
+}
+
+#[cfg(feature = "wasm")]
+impl Settings {
+    /// the settings saved in the local storage, if there are any
+    pub fn load() -> Option<Self> {
+        let storage = sauron::window().local_storage().ok()??;
+        let saved = storage.get_item(STORAGE_KEY).ok()??;
+        match serde_json::from_str(&saved) {
+            Ok(settings) => Some(settings),
+            Err(e) => {
+                log::warn!("invalid saved settings: {}", e);
+                None
+            }
+        }
+    }
+
+    /// save the settings in the local storage, so they are kept across visits
+    pub fn save(&self) {
+        let storage = match sauron::window().local_storage() {
+            Ok(Some(storage)) => storage,
+            _ => {
+                log::warn!("local storage is not available, settings are not saved");
+                return;
+            }
+        };
+        let saved = serde_json::to_string(self).expect("must serialize settings");
+        if storage.set_item(STORAGE_KEY, &saved).is_err() {
+            log::warn!("unable to save the settings");
+        }
+    }
+}
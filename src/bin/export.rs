@@ -0,0 +1,218 @@
+//! Takes a snapshot of the api, so the app can be hosted where there is no api access.
+//!
+//! ```sh
+//! cargo run --bin export --features export -- [DIR] [--stories N]
+//! ```
+//! The first `N` stories of every story list are written to `DIR` (`snapshot` by default)
+//! with all their comments, poll options and the users who posted them,
+//! along with the latest submissions of those users.
+//! The api can be configured with an `ApiConfig` json in `HN_API_CONFIG`.
+//! The app reads the snapshot when `snapshot` is set in its `ApiConfig`.
+#![deny(warnings)]
+use futures::future::join_all;
+use hackernews_sauron::{
+    api::{
+        self,
+        ApiConfig,
+        HnClient,
+        Manifest,
+        ServerError,
+        MANIFEST_FILE,
+        SNAPSHOT_VERSION,
+    },
+    types::{
+        Item,
+        StorySorting,
+    },
+};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    env,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// how many stories of each list are exported by default, that is three pages
+const DEFAULT_STORIES: usize = api::STORIES_COUNT * 3;
+/// how many items are fetched at a time
+const BATCH_SIZE: usize = 50;
+
+struct Exporter {
+    client: HnClient,
+    dir: PathBuf,
+}
+
+impl Exporter {
+    fn config(&self) -> &ApiConfig {
+        self.client.config()
+    }
+
+    /// fetch the json of `url` and write it at the same path in the snapshot
+    async fn export(&self, url: &str) -> Result<Value, ServerError> {
+        let value = api::make_json_get_request::<Value>(&self.client, url).await?;
+        let path = url
+            .strip_prefix(self.config().base_url.as_str())
+            .unwrap_or(url)
+            .trim_start_matches('/');
+        write_json(&self.dir.join(path), &value)?;
+        Ok(value)
+    }
+
+    /// export the items which are not `seen` yet, along with all their descendants when
+    /// `with_descendants` is set. Returns the number of items written and their authors,
+    /// so the users of the stories and comments can be exported
+    async fn export_items(
+        &self,
+        item_ids: Vec<i64>,
+        seen: &mut HashSet<i64>,
+        with_descendants: bool,
+    ) -> (usize, HashSet<String>) {
+        let mut queue = item_ids
+            .into_iter()
+            .filter(|item_id| seen.insert(*item_id))
+            .collect::<Vec<_>>();
+        let mut exported = 0;
+        let mut authors = HashSet::new();
+
+        while !queue.is_empty() {
+            let batch = queue.split_off(queue.len().saturating_sub(BATCH_SIZE));
+            let results = join_all(batch.iter().map(|item_id| {
+                let url = self.config().item_url(*item_id);
+                async move { self.export(&url).await }
+            }))
+            .await;
+
+            for (item_id, result) in batch.iter().zip(results) {
+                let item = match result
+                    .and_then(|value| Ok(serde_json::from_value::<Item>(value)?))
+                {
+                    Ok(item) => item,
+                    Err(e) => {
+                        eprintln!("unable to export item {}: {}", item_id, e);
+                        continue;
+                    }
+                };
+                exported += 1;
+                let data = item.data();
+                authors.extend(data.by.clone());
+                if !with_descendants {
+                    continue;
+                }
+                for child in data.kids.iter().chain(data.parts.iter()) {
+                    if seen.insert(*child) {
+                        queue.push(*child);
+                    }
+                }
+            }
+            println!("exported {} items, {} to go", exported, queue.len());
+        }
+        (exported, authors)
+    }
+}
+
+fn write_json(path: &Path, value: &Value) -> Result<(), ServerError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_string(value)?)?;
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> Result<(), ServerError> {
+    let mut dir = PathBuf::from("snapshot");
+    let mut stories_count = DEFAULT_STORIES;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--stories" => {
+                stories_count = args
+                    .next()
+                    .and_then(|count| count.parse().ok())
+                    .expect("--stories must be followed by a number");
+            }
+            _ => dir = PathBuf::from(arg),
+        }
+    }
+
+    let config = match env::var("HN_API_CONFIG") {
+        Ok(config) => {
+            serde_json::from_str(&config).expect("HN_API_CONFIG must be a valid ApiConfig")
+        }
+        Err(_) => ApiConfig::default(),
+    };
+    let exporter = Exporter {
+        client: HnClient::from(config),
+        dir,
+    };
+
+    let mut story_ids = vec![];
+    let mut stories = vec![];
+    for sorting in StorySorting::all() {
+        let url = exporter.config().stories_url(sorting);
+        let ids = api::make_json_get_request::<Vec<i64>>(&exporter.client, &url).await?;
+        // only the exported stories are listed, so every page of the list can be shown
+        let ids = ids.into_iter().take(stories_count).collect::<Vec<_>>();
+        let path = url
+            .strip_prefix(exporter.config().base_url.as_str())
+            .unwrap_or(&url)
+            .trim_start_matches('/');
+        write_json(&exporter.dir.join(path), &serde_json::to_value(&ids)?)?;
+        stories.push((sorting.to_str().to_string(), ids.len()));
+        story_ids.extend(ids);
+    }
+    story_ids.sort_unstable();
+    story_ids.dedup();
+
+    let mut seen = HashSet::new();
+    let (mut items, authors) = exporter.export_items(story_ids, &mut seen, true).await;
+
+    let exporter = &exporter;
+    let users = join_all(authors.iter().map(|author| {
+        let url = exporter.config().user_url(author);
+        async move { exporter.export(&url).await }
+    }))
+    .await
+    .into_iter()
+    .filter_map(Result::ok)
+    .collect::<Vec<_>>();
+
+    // the latest submissions are displayed on the user pages, without their replies
+    let submissions = users
+        .iter()
+        .flat_map(|user| {
+            user["submitted"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .take(api::USER_SUBMISSIONS)
+                .filter_map(Value::as_i64)
+        })
+        .collect::<Vec<_>>();
+    items += exporter.export_items(submissions, &mut seen, false).await.0;
+    let users = users.len();
+
+    let manifest = Manifest {
+        version: SNAPSHOT_VERSION,
+        created_at: chrono::Utc::now(),
+        source: exporter.config().base_url.clone(),
+        stories,
+        items,
+        users,
+    };
+    write_json(
+        &exporter.dir.join(MANIFEST_FILE),
+        &serde_json::to_value(&manifest)?,
+    )?;
+    println!(
+        "exported {} items and {} users to {}",
+        items,
+        users,
+        exporter.dir.display()
+    );
+    Ok(())
+}
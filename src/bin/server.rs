@@ -0,0 +1,222 @@
+//! Serves the app with its content already rendered, the serialized state of the app
+//! is embedded in the page so the client picks up from there instead of refetching.
+//!
+//! Every page of the app is served at its path, ie: `/item/8863` or `/search?q=rust`,
+//! and the client keeps routing with the same paths through the history api.
+//!
+//! The static files (`index.html`, `style.css` and the `pkg` built by wasm-pack) are served
+//! from the directory in `HN_STATIC_DIR`, which defaults to the current directory.
+//! The server listens to the port in `PORT`, and the api can be configured with
+//! an `ApiConfig` json in `HN_API_CONFIG`.
+#![deny(warnings)]
+use hackernews_sauron::{
+    api::{
+        self,
+        ApiConfig,
+        HnClient,
+        ServerError,
+    },
+    route::{
+        Route,
+        Router,
+        RouterMode,
+    },
+    sauron::{
+        prelude::*,
+        vdom::Leaf,
+    },
+    App,
+    FetchStatus,
+};
+use std::{
+    convert::Infallible,
+    env,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::Arc,
+};
+use warp::{
+    http::StatusCode,
+    Filter,
+};
+
+const DEFAULT_PORT: u16 = 3030;
+
+struct Server {
+    /// the index.html the rendered app is put into
+    template: String,
+    client: HnClient,
+    /// the pages are served at their path, which the client keeps routing with
+    router: Router,
+}
+
+impl Server {
+    /// the app showing the content of `route`
+    async fn fetch_route(&self, route: Route) -> Result<App, ServerError> {
+        let client = &self.client;
+        match route {
+            Route::Home => {
+                api::get_stories_page(client, Default::default(), 1, api::STORIES_COUNT)
+                    .await
+                    .map(App::with_stories)
+            }
+            Route::Stories { sorting, page } => {
+                api::get_stories_page(client, sorting, page, api::STORIES_COUNT)
+                    .await
+                    .map(App::with_stories)
+            }
+            Route::Story(story_id) => {
+                api::get_story(client, story_id).await.map(App::with_story)
+            }
+            Route::Comment(comment_id) => api::get_comment(client, comment_id)
+                .await
+                .map(App::with_comment_permalink),
+            Route::User(username) => api::get_user_page(client, &username)
+                .await
+                .map(App::with_user_page),
+            Route::Search(search) => api::get_search_results(client, &search)
+                .await
+                .map(App::with_search_results),
+        }
+    }
+
+    /// the page of `app`, or the error page if the content could not be fetched
+    fn respond(&self, app: Result<App, ServerError>) -> impl warp::Reply {
+        let (app, status) = match app {
+            Ok(app) => (app, StatusCode::OK),
+            Err(e) => {
+                eprintln!("unable to fetch the content: {}", e);
+                let status = match &e {
+                    ServerError::NotFound { .. } => StatusCode::NOT_FOUND,
+                    _ => StatusCode::BAD_GATEWAY,
+                };
+                let mut app = App::default();
+                app.content = FetchStatus::Error(e);
+                (app, status)
+            }
+        };
+        self.render(app, status)
+    }
+
+    /// the not found page of `location`, which is not a page of the app nor a static file
+    fn not_found(&self, location: &str) -> Result<impl warp::Reply, Infallible> {
+        let app = App::with_unknown_route(self.router.unknown(location));
+        Ok(self.render(app, StatusCode::NOT_FOUND))
+    }
+
+    fn render(&self, mut app: App, status: StatusCode) -> impl warp::Reply {
+        app.set_api_config(self.client.config().clone());
+        app.set_router(self.router.clone());
+        let page = render_page(&self.template, &app);
+        warp::reply::with_status(warp::reply::html(page), status)
+    }
+}
+
+/// put the rendered `app` in place of the body of `template`,
+/// and hand its serialized state to the `main` of the client
+fn render_page(template: &str, app: &App) -> String {
+    let state = serde_json::to_string(app).expect("must serialize the app");
+    // passed as a js string literal, where `</` is escaped so the state can't close the script
+    let state_literal = serde_json::to_string(&state)
+        .expect("must serialize the state")
+        .replace("</", "<\\/");
+    let mut body = app.view();
+    escape_text(&mut body);
+    let body = body.render_to_string();
+
+    let body_start = template.find("<body").unwrap_or(template.len());
+    let body_end = template
+        .find("</body>")
+        .map(|end| end + "</body>".len())
+        .unwrap_or(template.len());
+
+    // the pages are served from nested paths such as /item/123,
+    // so the relative urls of the assets are resolved from the root
+    let head = template[..body_start]
+        .replacen("<head>", "<head>\n       <base href=\"/\"/>", 1)
+        .replacen("main(``)", &format!("main({})", state_literal), 1);
+
+    format!("{}{}{}", head, body, &template[body_end..])
+}
+
+/// the text nodes are rendered as is, which is fine for the dom but not for html,
+/// so a title such as `<script>` has to be escaped before it is rendered
+fn escape_text<MSG>(node: &mut Node<MSG>) {
+    match node {
+        Node::Leaf(Leaf::Text(text)) => {
+            *text = html_escape::encode_text(text).into_owned().into();
+        }
+        Node::Leaf(Leaf::NodeList(nodes)) | Node::Leaf(Leaf::Fragment(nodes)) => {
+            nodes.iter_mut().for_each(escape_text);
+        }
+        Node::Element(element) => {
+            element.children_mut().iter_mut().for_each(escape_text);
+        }
+        Node::Leaf(_) => (),
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    let static_dir = PathBuf::from(
+        env::var("HN_STATIC_DIR").unwrap_or_else(|_| String::from(".")),
+    );
+    let port = env::var("PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(DEFAULT_PORT);
+    let config = match env::var("HN_API_CONFIG") {
+        Ok(config) => {
+            serde_json::from_str(&config).expect("HN_API_CONFIG must be a valid ApiConfig")
+        }
+        Err(_) => ApiConfig::default(),
+    };
+    let template = std::fs::read_to_string(static_dir.join("index.html"))
+        .expect("must read index.html from the static dir");
+
+    let server = Arc::new(Server {
+        template,
+        client: HnClient::from(config),
+        router: Router::new(RouterMode::History, ""),
+    });
+    let with_server = warp::any().map(move || server.clone());
+
+    // the path and the query of the request, which is what is routed
+    let location = warp::path::full()
+        .and(
+            warp::query::raw()
+                .or(warp::any().map(String::new))
+                .unify(),
+        )
+        .map(|path: warp::path::FullPath, query: String| {
+            if query.is_empty() {
+                path.as_str().to_string()
+            } else {
+                format!("{}?{}", path.as_str(), query)
+            }
+        });
+
+    // the paths which are not a page of the app are left to the static files
+    let page = location.and(with_server.clone()).and_then(
+        |location: String, server: Arc<Server>| async move {
+            match server.router.route(&location) {
+                Ok(route) => Ok(server.respond(server.fetch_route(route).await)),
+                Err(_) => Err(warp::reject::not_found()),
+            }
+        },
+    );
+
+    let not_found = location.and(with_server).and_then(
+        |location: String, server: Arc<Server>| async move {
+            server.not_found(&location)
+        },
+    );
+
+    let routes = warp::get().and(
+        page.or(warp::fs::dir(static_dir)).or(not_found),
+    );
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+    println!("serving at http://{}", addr);
+    warp::serve(routes).run(addr).await;
+}
@@ -6,16 +6,23 @@ pub mod api;
 pub mod types;
 
 // App modules (previously from client crate)
-pub use app::{App, Msg};
+pub use app::{App, Content, FetchStatus, Msg};
 use sauron::prelude::*;
 pub use sauron;
 
 mod app;
+pub mod route;
 pub mod util;
 
 /// The serialized_state is optionally supplied for server-side rendering hydration.
 /// For pure client-side applications, an empty string can be passed to start with App::default().
 /// The app will then initialize with default state and begin client-side routing.
+///
+/// The api endpoints can be supplied through the `api_config` field of the serialized_state,
+/// or through a `<meta name="hn-api-config" content='{"base_url": "..."}'>` tag in the page,
+/// the meta tag takes precedence.
+/// Likewise, the pages are kept in the hash of the url unless a
+/// `<meta name="hn-router" content='{"mode": "history", "base_path": "/hn"}'>` tag says otherwise.
 #[cfg_attr(feature = "wasm", wasm_bindgen)]
 pub async fn main(serialized_state: String) {
     #[cfg(feature = "wasm-bindgen")]
@@ -24,12 +31,21 @@ pub async fn main(serialized_state: String) {
         console_error_panic_hook::set_once();
     }
 
-    let app = match serde_json::from_str::<App>(&serialized_state) {
+    #[allow(unused_mut)]
+    let mut app = match serde_json::from_str::<App>(&serialized_state) {
         Ok(app_state) => app_state,
         Err(e) => {
             log::warn!("error: {}", e);
             App::default()
         }
     };
+    #[cfg(feature = "wasm")]
+    if let Some(config) = api::ApiConfig::from_meta_tag() {
+        app.set_api_config(config);
+    }
+    #[cfg(feature = "wasm")]
+    if let Some(router) = route::Router::from_meta_tag() {
+        app.set_router(router);
+    }
     Program::replace_mount(app, &sauron::dom::util::body());
 }
\ No newline at end of file
@@ -0,0 +1,322 @@
+//! The urls of the app, parsed into a [`Route`] and assembled back from it.
+//!
+//! The server serves the routes as paths, ie: `/item/8863`, while the browser keeps
+//! them either in the hash, ie: `#item/8863`, or in the path as well, depending on
+//! the [`RouterMode`] of its [`Router`]. Both are parsed the same way, and the urls
+//! assembled by [`Route::to_url`] and [`Route::to_path`] are parsed back into the same route.
+use crate::{
+    api::SearchQuery,
+    types::StorySorting,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use thiserror::Error;
+
+/// The last page number of a story listing which is routed. The listings of the api
+/// have 500 stories at most, so the pages after it are always empty
+pub const MAX_PAGE: usize = 1_000;
+
+/// A page of the app
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum Route {
+    /// the first page of the top stories, at the root of the app
+    Home,
+    /// a page of a story listing, where the first page is 1
+    Stories { sorting: StorySorting, page: usize },
+    /// a story with its comments
+    Story(i64),
+    /// a comment with its replies
+    Comment(i64),
+    /// the profile and submissions of a user.
+    /// Usernames are made of letters, digits, dashes and underscores,
+    /// so they are put in the url as is
+    User(String),
+    Search(SearchQuery),
+}
+
+/// A url which is not a page of the app, along with the page it was most likely meant to be
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct UnknownRoute {
+    pub url: String,
+    pub suggestion: Option<Route>,
+}
+
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RouteError {
+    #[error("no route matches {0}")]
+    Unknown(String),
+    #[error("{0} is not a valid id")]
+    InvalidId(String),
+    #[error("{0} is not a valid page number")]
+    InvalidPage(String),
+    #[error("{0} is not a valid search")]
+    InvalidSearch(String),
+}
+
+impl Route {
+    /// parse a path, ie: `/item/8863`, a hash, ie: `#top/page/2`, or a url with either of them.
+    /// When there is a hash, only the hash is routed.
+    pub fn parse(url: &str) -> Result<Self, RouteError> {
+        let target = match url.split_once('#') {
+            Some((_, hash)) => hash,
+            None => url,
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        // the empty segments of leading, trailing and repeated slashes are skipped
+        let segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect::<Vec<_>>();
+        match segments.as_slice() {
+            [] => Ok(Route::Home),
+            ["item", id] => parse_id(id).map(Route::Story),
+            ["comment", id] => parse_id(id).map(Route::Comment),
+            ["user", username] => Ok(Route::User(username.to_string())),
+            ["search"] => SearchQuery::from_query(query)
+                .map(Route::Search)
+                .ok_or_else(|| RouteError::InvalidSearch(query.to_string())),
+            [sorting] => Ok(Route::Stories {
+                sorting: parse_sorting(sorting, url)?,
+                page: 1,
+            }),
+            [sorting, "page", page] => Ok(Route::Stories {
+                sorting: parse_sorting(sorting, url)?,
+                page: parse_page(page)?,
+            }),
+            _ => Err(RouteError::Unknown(url.to_string())),
+        }
+    }
+
+    /// the route which is the closest to `url`, when it is not a route itself.
+    /// The misspelled segments are corrected, the characters trailing the digits of the ids
+    /// and page numbers are cut off and the segments left over are dropped, ie: `#itme/8863x/foo`
+    /// is nearest to `#item/8863`. Nothing is suggested for the ids with anything else in between
+    /// their digits, such as `#comment/12.5`. The front page is not suggested, as it is always linked to
+    pub fn nearest(url: &str) -> Option<Route> {
+        if let Ok(route) = Route::parse(url) {
+            return Some(route).filter(|route| *route != Route::Home);
+        }
+        let target = match url.split_once('#') {
+            Some((_, hash)) => hash,
+            None => url,
+        };
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let mut segments = path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .map(str::to_string)
+            .collect::<Vec<_>>();
+        if let Some(first) = segments.first_mut() {
+            *first = first.to_lowercase();
+            if let Some(keyword) = nearest_keyword(first) {
+                *first = keyword;
+            }
+        }
+        for i in 1..segments.len() {
+            if ["item", "comment", "page"].contains(&segments[i - 1].as_str()) {
+                let digits = segments[i].trim_end_matches(|c: char| !c.is_ascii_digit());
+                // with anything else between the digits, it is another number altogether
+                if !digits.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+                segments[i] = digits.to_string();
+            }
+        }
+        while !segments.is_empty() {
+            match Route::parse(&format!("/{}?{}", segments.join("/"), query)) {
+                Ok(Route::Home) => return None,
+                Ok(route) => return Some(route),
+                Err(_) => {
+                    segments.pop();
+                }
+            }
+        }
+        None
+    }
+
+    /// the hash url of this route, ie: `#item/8863`
+    pub fn to_url(&self) -> String {
+        format!("#{}", &self.to_path()[1..])
+    }
+
+    /// the path of this route from the root of the app, ie: `/item/8863`
+    pub fn to_path(&self) -> String {
+        match self {
+            Route::Home => String::from("/"),
+            Route::Stories { sorting, page } if *page > 1 => {
+                format!("/{}/page/{}", sorting.to_str(), page)
+            }
+            Route::Stories { sorting, .. } => format!("/{}", sorting.to_str()),
+            Route::Story(story_id) => format!("/item/{}", story_id),
+            Route::Comment(comment_id) => format!("/comment/{}", comment_id),
+            Route::User(username) => format!("/user/{}", username),
+            Route::Search(search) => format!("/search?{}", search.to_query()),
+        }
+    }
+}
+
+/// Where the route is kept in the url of the browser
+#[derive(Copy, Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RouterMode {
+    /// in the hash, ie: `/#item/8863`, which works with any static file server
+    #[default]
+    Hash,
+    /// in the path, ie: `/item/8863`, through the `pushState` of the history.
+    /// Every path has to be served with the `index.html` of the app
+    History,
+}
+
+/// How the routes are put in the url of the browser, and read back from it
+#[derive(Clone, Debug, PartialEq, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Router {
+    pub mode: RouterMode,
+    /// the path the app is served from, ie: `/hackernews-sauron` on GitHub Pages,
+    /// which is empty when it is served from the root of the domain
+    pub base_path: String,
+}
+
+#[cfg(feature = "wasm")]
+const META_NAME: &str = "hn-router";
+
+impl Router {
+    pub fn new(mode: RouterMode, base_path: &str) -> Self {
+        Self {
+            mode,
+            base_path: base_path.trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// the href of the links to `route`
+    pub fn href(&self, route: &Route) -> String {
+        match self.mode {
+            // relative to the page of the app when it is served from the root
+            RouterMode::Hash if self.base_path.is_empty() => route.to_url(),
+            RouterMode::Hash => format!("{}/{}", self.base_path, route.to_url()),
+            RouterMode::History => format!("{}{}", self.base_path, route.to_path()),
+        }
+    }
+
+    /// the route of `location`, made of the path, the query and the hash of the url
+    pub fn route(&self, location: &str) -> Result<Route, RouteError> {
+        let routed = self.routed(location);
+        match Route::parse(routed) {
+            // the app can be served from any path, which is
+            // its root as long as there is no hash
+            Err(_) if self.mode == RouterMode::Hash && !routed.starts_with('#') => {
+                Ok(Route::Home)
+            }
+            route => route,
+        }
+    }
+
+    /// the page `location` was most likely meant to be, when it is not a route
+    pub fn unknown(&self, location: &str) -> UnknownRoute {
+        UnknownRoute {
+            url: location.to_string(),
+            suggestion: Route::nearest(self.routed(location)),
+        }
+    }
+
+    /// the part of `location` which holds the route
+    fn routed<'a>(&self, location: &'a str) -> &'a str {
+        match location.split_once('#') {
+            Some((path, hash)) if self.mode == RouterMode::Hash && !hash.is_empty() => {
+                &location[path.len()..]
+            }
+            Some((path, _)) => self.strip_base(path),
+            None => self.strip_base(location),
+        }
+    }
+
+    /// the part of `path` after the base path, without the `index.html` the app may be served as
+    fn strip_base<'a>(&self, path: &'a str) -> &'a str {
+        let path = match path.strip_prefix(self.base_path.as_str()) {
+            Some(rest) if rest.is_empty() || rest.starts_with(['/', '?']) => rest,
+            _ => path,
+        };
+        match path.strip_prefix("/index.html") {
+            Some(rest) if rest.is_empty() || rest.starts_with('?') => rest,
+            _ => path,
+        }
+    }
+
+    /// read the router from the json content of `<meta name="hn-router">`
+    #[cfg(feature = "wasm")]
+    pub fn from_meta_tag() -> Option<Self> {
+        let router = crate::util::meta_json::<Router>(META_NAME)?;
+        Some(Router::new(router.mode, &router.base_path))
+    }
+}
+
+fn parse_id(id: &str) -> Result<i64, RouteError> {
+    id.parse()
+        .map_err(|_| RouteError::InvalidId(id.to_string()))
+}
+
+fn parse_page(page: &str) -> Result<usize, RouteError> {
+    match page.parse() {
+        Ok(page) if (1..=MAX_PAGE).contains(&page) => Ok(page),
+        _ => Err(RouteError::InvalidPage(page.to_string())),
+    }
+}
+
+/// the segment which `segment` is a misspelling of, if it is not one already
+fn nearest_keyword(segment: &str) -> Option<String> {
+    let keywords = ["item", "comment", "user", "search"]
+        .iter()
+        .map(|keyword| keyword.to_string())
+        .chain(
+            StorySorting::all()
+                .into_iter()
+                .map(|sorting| sorting.to_str().to_string()),
+        );
+    // a typo is a couple of edits at most, and a single one in the shorter words
+    let max_distance = if segment.chars().count() > 4 { 2 } else { 1 };
+    keywords
+        .map(|keyword| {
+            let distance = edit_distance(segment, &keyword);
+            (keyword, distance)
+        })
+        .filter(|(_, distance)| (1..=max_distance).contains(distance))
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(keyword, _)| keyword)
+}
+
+/// the number of characters to insert, remove, replace or swap with
+/// the one next to them to turn `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // distances[i][j] is the distance between the first i chars of a and the first j of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let replace = distances[i - 1][j - 1] + usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = replace
+                .min(distances[i - 1][j] + 1)
+                .min(distances[i][j - 1] + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+fn parse_sorting(sorting: &str, url: &str) -> Result<StorySorting, RouteError> {
+    StorySorting::all()
+        .into_iter()
+        .find(|s| s.to_str() == sorting)
+        .ok_or_else(|| RouteError::Unknown(url.to_string()))
+}
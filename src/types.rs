@@ -1,8 +1,12 @@
+use crate::api::ServerError;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, Default, Deserialize, Serialize,
+)]
 pub enum StorySorting {
+    #[default]
     Top,
     New,
     Best,
@@ -11,12 +15,12 @@ pub enum StorySorting {
     Job,
 }
 
-const TOP: &'static str = "top";
-const BEST: &'static str = "best";
-const NEW: &'static str = "new";
-const SHOW: &'static str = "show";
-const ASK: &'static str = "ask";
-const JOB: &'static str = "job";
+const TOP: &str = "top";
+const BEST: &str = "best";
+const NEW: &str = "new";
+const SHOW: &str = "show";
+const ASK: &str = "ask";
+const JOB: &str = "job";
 
 impl StorySorting {
     /// return all of the story sorting possible
@@ -30,11 +34,6 @@ impl StorySorting {
             StorySorting::Job,
         ]
     }
-    /// match url to StorySorting (supports both hash and path routing)
-    pub fn from_url(url: &str) -> Option<Self> {
-        let hash_url = url.strip_prefix("#").unwrap_or(url);
-        Self::all().into_iter().find(|s| hash_url == s.to_str() || url == s.to_url())
-    }
     /// return the str for assembling paths in warp
     pub fn to_str(&self) -> &str {
         match self {
@@ -46,15 +45,143 @@ impl StorySorting {
             StorySorting::Job => JOB,
         }
     }
+}
+
+/// The order the stories of a page are displayed in
+#[derive(
+    Copy, Clone, Debug, PartialEq, Default, Deserialize, Serialize,
+)]
+pub enum StoryOrder {
+    /// the ranking of the listing as returned by the api
+    #[default]
+    Rank,
+    /// highest score first
+    Score,
+    /// most comments first
+    Comments,
+    /// newest first
+    Age,
+}
 
-    pub fn to_url(&self) -> String {
-        format!("#{}", self.to_str())
+impl StoryOrder {
+    pub fn all() -> Vec<Self> {
+        vec![
+            StoryOrder::Rank,
+            StoryOrder::Score,
+            StoryOrder::Comments,
+            StoryOrder::Age,
+        ]
     }
+
+    pub fn to_str(&self) -> &str {
+        match self {
+            StoryOrder::Rank => "rank",
+            StoryOrder::Score => "score",
+            StoryOrder::Comments => "comments",
+            StoryOrder::Age => "age",
+        }
+    }
+}
+
+/// A page of stories of a story listing
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StoryList {
+    pub sorting: StorySorting,
+    /// the page number, starting at 1
+    pub page: usize,
+    pub page_size: usize,
+    /// all the story ids of the listing, so the next pages can be fetched without
+    /// fetching the listing again
+    pub story_ids: Vec<i64>,
+    /// the stories in this page, in the same order as their ids in the listing
+    pub stories: Vec<StoryItem>,
+    /// the order the stories are displayed in
+    #[serde(default)]
+    pub order: StoryOrder,
+    /// the stories of this page which could not be fetched
+    #[serde(default)]
+    pub failed: Vec<FailedFetch>,
 }
 
-impl Default for StorySorting {
-    fn default() -> Self {
-        StorySorting::Top
+impl StoryList {
+    /// the `page` of a listing with `story_ids`, with none of its stories fetched yet
+    pub fn new(
+        sorting: StorySorting,
+        story_ids: Vec<i64>,
+        page: usize,
+        page_size: usize,
+    ) -> Self {
+        Self {
+            sorting,
+            page: page.max(1),
+            page_size,
+            story_ids,
+            stories: vec![],
+            order: StoryOrder::default(),
+            failed: vec![],
+        }
+    }
+
+    /// add the stories which were fetched again, keeping the order of the listing
+    pub fn merge_stories(&mut self, stories: Vec<StoryItem>) {
+        for story in stories {
+            if !self.stories.iter().any(|loaded| loaded.id == story.id) {
+                self.stories.push(story);
+            }
+        }
+        let story_ids = &self.story_ids;
+        self.stories.sort_by_key(|story| {
+            story_ids
+                .iter()
+                .position(|id| *id == story.id)
+                .unwrap_or(story_ids.len())
+        });
+    }
+
+    /// the position of the first story of this page in the whole listing
+    pub fn offset(&self) -> usize {
+        // the pages far past the end of the listing are empty rather than overflowing
+        self.page.saturating_sub(1).saturating_mul(self.page_size)
+    }
+
+    /// the story ids of this page
+    pub fn page_ids(&self) -> &[i64] {
+        let start = self.offset().min(self.story_ids.len());
+        let end = start.saturating_add(self.page_size).min(self.story_ids.len());
+        &self.story_ids[start..end]
+    }
+
+    /// the stories of this page in display order, paired with their position in the listing
+    pub fn ranked_stories(&self) -> Vec<(usize, &StoryItem)> {
+        let page_ids = self.page_ids();
+        let mut ranked = self
+            .stories
+            .iter()
+            .map(|story| {
+                let index = page_ids
+                    .iter()
+                    .position(|id| *id == story.id)
+                    .unwrap_or(page_ids.len());
+                (self.offset().saturating_add(index + 1), story)
+            })
+            .collect::<Vec<_>>();
+        match self.order {
+            StoryOrder::Rank => ranked.sort_by_key(|(rank, _)| *rank),
+            StoryOrder::Score => {
+                ranked.sort_by_key(|(_, story)| std::cmp::Reverse(story.score))
+            }
+            StoryOrder::Comments => ranked
+                .sort_by_key(|(_, story)| std::cmp::Reverse(story.descendants)),
+            StoryOrder::Age => {
+                ranked.sort_by_key(|(_, story)| std::cmp::Reverse(story.time))
+            }
+        }
+        ranked
+    }
+
+    /// whether there are stories after this page
+    pub fn has_more(&self) -> bool {
+        self.offset().saturating_add(self.page_size) < self.story_ids.len()
     }
 }
 
@@ -74,12 +201,115 @@ pub struct StoryPageData {
     pub time: DateTime<Utc>,
     #[serde(default)]
     pub kids: Vec<i64>,
-    pub r#type: String,
+    /// the poll options, if this is a poll
+    #[serde(default)]
+    pub parts: Vec<i64>,
+    pub kind: ItemKind,
     #[serde(default)]
     pub comments: Vec<Comment>,
+    /// the fetched poll options, in the same order as the parts
+    #[serde(default)]
+    pub poll_options: Vec<PollOption>,
+    /// the top-level comments that were posted after the page was loaded
+    #[serde(default)]
+    pub new_kids: Vec<i64>,
+    /// when the story was saved for offline reading, if it was
+    #[serde(default, with = "chrono::serde::ts_seconds_option")]
+    pub saved_at: Option<DateTime<Utc>>,
+    /// the comments and poll options which could not be fetched
+    #[serde(default)]
+    pub failed: Vec<FailedFetch>,
 }
 
 impl StoryPageData {
+    /// the ids of the top-level comments which are not loaded yet, excluding the new comments
+    pub fn unloaded_kids(&self) -> Vec<i64> {
+        unloaded_kids(&self.kids, &self.comments)
+            .into_iter()
+            .filter(|kid| !self.new_kids.contains(kid))
+            .collect()
+    }
+
+    /// apply the latest state of this story, the kids that were not there
+    /// before are kept as the new comments
+    pub fn patch(&mut self, story: &StoryItem) {
+        self.title = story.title.to_owned();
+        self.url = story.url.to_owned();
+        self.text = story.text.to_owned();
+        self.score = story.score;
+        self.descendants = story.descendants;
+        let new_kids = story
+            .kids
+            .iter()
+            .filter(|kid| !self.kids.contains(kid))
+            .copied()
+            .collect::<Vec<_>>();
+        self.new_kids.extend(new_kids);
+        self.kids = story.kids.to_owned();
+    }
+
+    /// the ids of all the loaded comments
+    pub fn comment_ids(&self) -> Vec<i64> {
+        self.comments.iter().flat_map(|comment| comment.ids()).collect()
+    }
+
+    /// find the comment with `comment_id` in the loaded comment tree
+    pub fn find_comment(&self, comment_id: i64) -> Option<&Comment> {
+        self.comments
+            .iter()
+            .find_map(|comment| comment.find(comment_id))
+    }
+
+    /// find the comment with `comment_id` in the loaded comment tree
+    pub fn find_comment_mut(&mut self, comment_id: i64) -> Option<&mut Comment> {
+        self.comments
+            .iter_mut()
+            .find_map(|comment| comment.find_mut(comment_id))
+    }
+
+    /// the id of the story or the loaded comment which `comment_id` is a reply to
+    pub fn parent_of(&self, comment_id: i64) -> Option<i64> {
+        if self.kids.contains(&comment_id) {
+            return Some(self.id);
+        }
+        self.comments
+            .iter()
+            .find_map(|comment| comment.find_parent(comment_id))
+            .map(|parent| parent.id)
+    }
+
+    /// add `replies` to the comment tree, under the story itself or the comment with `parent_id`.
+    /// Returns false if the parent is not in the loaded comment tree.
+    pub fn insert_replies(&mut self, parent_id: i64, replies: Vec<Comment>) -> bool {
+        if parent_id == self.id {
+            merge_replies(&self.kids, &mut self.comments, replies);
+            let comments = &self.comments;
+            self.new_kids
+                .retain(|kid| !comments.iter().any(|comment| comment.id == *kid));
+            true
+        } else if let Some(parent) = self.find_comment_mut(parent_id) {
+            parent.insert_replies(parent_id, replies)
+        } else {
+            false
+        }
+    }
+
+    /// add the poll options which were fetched again, keeping the order of the parts
+    pub fn merge_poll_options(&mut self, poll_options: Vec<PollOption>) {
+        for option in poll_options {
+            if !self.poll_options.iter().any(|loaded| loaded.id == option.id) {
+                self.poll_options.push(option);
+            }
+        }
+        let parts = &self.parts;
+        self.poll_options.sort_by_key(|option| {
+            parts
+                .iter()
+                .position(|part| *part == option.id)
+                .unwrap_or(parts.len())
+        });
+    }
+
     /// derive a preview of this StoragePageData
     pub fn preview(&self) -> StoryItem {
         StoryItem {
@@ -92,7 +322,8 @@ impl StoryPageData {
             descendants: self.descendants,
             time: self.time.to_owned(),
             kids: self.kids.to_owned(),
-            r#type: self.r#type.to_owned(),
+            parts: self.parts.to_owned(),
+            kind: self.kind,
         }
     }
 }
@@ -111,38 +342,311 @@ pub struct Comment {
     pub kids: Vec<i64>,
     #[serde(default)]
     pub sub_comments: Vec<Comment>,
-    pub r#type: String,
+    /// a deleted comment only has its id, time and kids left
+    #[serde(default)]
+    pub deleted: bool,
+    /// flagged or killed by the moderators, only shown with showdead
+    #[serde(default)]
+    pub dead: bool,
 }
 
 impl Comment {
-    /// attempt to extract comment id from url (supports both hash and path routing)
-    pub fn id_from_url(url: &str) -> Option<i64> {
-        let target_url = url.strip_prefix("#").unwrap_or(url);
-        if target_url.starts_with("comment/") || url.starts_with("/comment") {
-            let splinters = if target_url.starts_with("comment/") {
-                target_url.split("/").collect::<Vec<_>>()
-            } else {
-                url.split("/").collect::<Vec<_>>()
-            };
-            if splinters.len() >= 2 {
-                if target_url.starts_with("comment/") {
-                    splinters[1].parse::<i64>().ok()
-                } else {
-                    splinters[2].parse::<i64>().ok()
-                }
-            } else {
-                None
-            }
+    /// the ids of the replies which are not loaded yet
+    pub fn unloaded_kids(&self) -> Vec<i64> {
+        unloaded_kids(&self.kids, &self.sub_comments)
+    }
+
+    /// apply the latest state of this comment, new replies show up as not loaded
+    pub fn patch(&mut self, comment: &Comment) {
+        self.by = comment.by.to_owned();
+        self.text = comment.text.to_owned();
+        self.kids = comment.kids.to_owned();
+        self.deleted = comment.deleted;
+        self.dead = comment.dead;
+    }
+
+    /// whether this comment is rendered as a placeholder in place of its content
+    pub fn is_hidden(&self, show_dead: bool) -> bool {
+        self.deleted || (self.dead && !show_dead)
+    }
+
+    /// whether there is nothing to show for this comment, that is it is hidden
+    /// and has no replies which would need it as a placeholder
+    pub fn is_empty(&self, show_dead: bool) -> bool {
+        self.is_hidden(show_dead) && self.kids.is_empty()
+    }
+
+    /// the id of this comment and all its loaded replies
+    pub fn ids(&self) -> Vec<i64> {
+        std::iter::once(self.id)
+            .chain(self.sub_comments.iter().flat_map(|sub| sub.ids()))
+            .collect()
+    }
+
+    /// find the comment with `comment_id` in this comment and its replies
+    pub fn find(&self, comment_id: i64) -> Option<&Comment> {
+        if self.id == comment_id {
+            return Some(self);
+        }
+        self.sub_comments
+            .iter()
+            .find_map(|sub| sub.find(comment_id))
+    }
+
+    /// find the comment which `comment_id` is a reply to, in this comment and its replies
+    pub fn find_parent(&self, comment_id: i64) -> Option<&Comment> {
+        if self.kids.contains(&comment_id) {
+            return Some(self);
+        }
+        self.sub_comments
+            .iter()
+            .find_map(|sub| sub.find_parent(comment_id))
+    }
+
+    /// find the comment with `comment_id` in this comment and its replies
+    pub fn find_mut(&mut self, comment_id: i64) -> Option<&mut Comment> {
+        if self.id == comment_id {
+            return Some(self);
+        }
+        self.sub_comments
+            .iter_mut()
+            .find_map(|sub| sub.find_mut(comment_id))
+    }
+
+    /// add `replies` under the comment with `parent_id`, which is either this comment or one
+    /// of its replies. Returns false if the parent is not found.
+    pub fn insert_replies(&mut self, parent_id: i64, replies: Vec<Comment>) -> bool {
+        if let Some(parent) = self.find_mut(parent_id) {
+            merge_replies(&parent.kids, &mut parent.sub_comments, replies);
+            true
         } else {
-            None
+            false
+        }
+    }
+}
+
+/// The kind of an item, as in the `type` field of the api
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ItemKind {
+    Story,
+    Comment,
+    Job,
+    Poll,
+    PollOpt,
+}
+
+/// An item of the api, tagged with its `type`.
+///
+/// Items that are deleted only have their `id`, `deleted`, `time` and
+/// `type` fields, so everything else is optional.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum Item {
+    Story(ItemData),
+    Comment(ItemData),
+    Job(ItemData),
+    Poll(ItemData),
+    PollOpt(ItemData),
+}
+
+/// The fields of an item, the ones that do not apply to its kind are left empty
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ItemData {
+    pub id: i64,
+    #[serde(default)]
+    pub deleted: bool,
+    /// flagged or killed by the moderators
+    #[serde(default)]
+    pub dead: bool,
+    pub by: Option<String>,
+    #[serde(default, with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+    pub text: Option<String>,
+    /// the comment or story this comment is replying to
+    pub parent: Option<i64>,
+    /// the poll this poll option belongs to
+    pub poll: Option<i64>,
+    #[serde(default)]
+    pub kids: Vec<i64>,
+    pub url: Option<String>,
+    #[serde(default)]
+    pub score: i64,
+    pub title: Option<String>,
+    /// the options of a poll
+    #[serde(default)]
+    pub parts: Vec<i64>,
+    /// the total number of comments of a story or poll
+    #[serde(default)]
+    pub descendants: i64,
+}
+
+impl Item {
+    pub fn kind(&self) -> ItemKind {
+        match self {
+            Item::Story(_) => ItemKind::Story,
+            Item::Comment(_) => ItemKind::Comment,
+            Item::Job(_) => ItemKind::Job,
+            Item::Poll(_) => ItemKind::Poll,
+            Item::PollOpt(_) => ItemKind::PollOpt,
         }
     }
 
-    pub fn to_url(comment_id: i64) -> String {
-        format!("#comment/{}", comment_id)
+    pub fn data(&self) -> &ItemData {
+        match self {
+            Item::Story(data)
+            | Item::Comment(data)
+            | Item::Job(data)
+            | Item::Poll(data)
+            | Item::PollOpt(data) => data,
+        }
+    }
+
+    pub fn into_data(self) -> ItemData {
+        match self {
+            Item::Story(data)
+            | Item::Comment(data)
+            | Item::Job(data)
+            | Item::Poll(data)
+            | Item::PollOpt(data) => data,
+        }
+    }
+
+    pub fn id(&self) -> i64 {
+        self.data().id
+    }
+
+    /// stories, jobs and polls are all displayed as stories
+    pub fn is_story(&self) -> bool {
+        matches!(self, Item::Story(_) | Item::Job(_) | Item::Poll(_))
+    }
+
+    /// convert into a story preview, if this is a story, job or poll
+    pub fn into_story(self) -> Option<StoryItem> {
+        if !self.is_story() {
+            return None;
+        }
+        let kind = self.kind();
+        let data = self.into_data();
+        Some(StoryItem {
+            id: data.id,
+            title: data.title.unwrap_or_default(),
+            url: data.url,
+            text: data.text,
+            by: data.by.unwrap_or_default(),
+            score: data.score,
+            descendants: data.descendants,
+            time: data.time,
+            kids: data.kids,
+            parts: data.parts,
+            kind,
+        })
+    }
+
+    /// convert into a story page with no comments loaded yet, if this is a story, job or poll
+    pub fn into_story_page(self) -> Option<StoryPageData> {
+        let story = self.into_story()?;
+        Some(StoryPageData {
+            id: story.id,
+            title: story.title,
+            url: story.url,
+            text: story.text,
+            by: story.by,
+            score: story.score,
+            descendants: story.descendants,
+            time: story.time,
+            kids: story.kids,
+            parts: story.parts,
+            kind: story.kind,
+            comments: vec![],
+            poll_options: vec![],
+            new_kids: vec![],
+            saved_at: None,
+            failed: vec![],
+        })
+    }
+
+    /// convert into a comment with no replies loaded yet, if this is a comment
+    pub fn into_comment(self) -> Option<Comment> {
+        match self {
+            Item::Comment(data) => Some(Comment {
+                id: data.id,
+                by: data.by.unwrap_or_default(),
+                text: data.text.unwrap_or_default(),
+                time: data.time,
+                kids: data.kids,
+                sub_comments: vec![],
+                deleted: data.deleted,
+                dead: data.dead,
+            }),
+            _ => None,
+        }
+    }
+
+    /// convert into a poll option, if this is a poll option
+    pub fn into_poll_option(self) -> Option<PollOption> {
+        match self {
+            Item::PollOpt(data) => Some(PollOption {
+                id: data.id,
+                poll: data.poll.unwrap_or_default(),
+                by: data.by.unwrap_or_default(),
+                text: data.text.unwrap_or_default(),
+                score: data.score,
+                time: data.time,
+            }),
+            _ => None,
+        }
     }
 }
 
+/// An option of a poll
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct PollOption {
+    pub id: i64,
+    /// the poll this option belongs to
+    pub poll: i64,
+    #[serde(default)]
+    pub by: String,
+    #[serde(default)]
+    pub text: String,
+    /// the number of votes
+    #[serde(default)]
+    pub score: i64,
+    #[serde(with = "chrono::serde::ts_seconds")]
+    pub time: DateTime<Utc>,
+}
+
+/// the kids which are not in the loaded comments
+fn unloaded_kids(kids: &[i64], loaded: &[Comment]) -> Vec<i64> {
+    kids.iter()
+        .filter(|kid| !loaded.iter().any(|comment| comment.id == **kid))
+        .copied()
+        .collect()
+}
+
+/// An item which could not be fetched, so it is missing from the page it belongs to
+///
+/// The replies of a story which failed are reported whatever their level in the comment tree
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct FailedFetch {
+    pub id: i64,
+    pub error: ServerError,
+}
+
+/// add the replies which are not loaded yet, keeping the order of the kids
+fn merge_replies(kids: &[i64], loaded: &mut Vec<Comment>, replies: Vec<Comment>) {
+    for reply in replies {
+        if !loaded.iter().any(|comment| comment.id == reply.id) {
+            loaded.push(reply);
+        }
+    }
+    loaded.sort_by_key(|comment| {
+        kids.iter()
+            .position(|kid| *kid == comment.id)
+            .unwrap_or(kids.len())
+    });
+}
+
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct StoryItem {
     pub id: i64,
@@ -159,36 +663,10 @@ pub struct StoryItem {
     pub time: DateTime<Utc>,
     #[serde(default)]
     pub kids: Vec<i64>,
-    pub r#type: String,
-}
-
-impl StoryItem {
-    /// attempt to extract story id from url (supports both hash and path routing)
-    pub fn id_from_url(url: &str) -> Option<i64> {
-        let target_url = url.strip_prefix("#").unwrap_or(url);
-        if target_url.starts_with("item/") || url.starts_with("/item") {
-            let splinters = if target_url.starts_with("item/") {
-                target_url.split("/").collect::<Vec<_>>()
-            } else {
-                url.split("/").collect::<Vec<_>>()
-            };
-            if splinters.len() >= 2 {
-                if target_url.starts_with("item/") {
-                    splinters[1].parse::<i64>().ok()
-                } else {
-                    splinters[2].parse::<i64>().ok()
-                }
-            } else {
-                None
-            }
-        } else {
-            None
-        }
-    }
-
-    pub fn to_url(story_id: i64) -> String {
-        format!("#item/{}", story_id)
-    }
+    /// the poll options, if this is a poll
+    #[serde(default)]
+    pub parts: Vec<i64>,
+    pub kind: ItemKind,
 }
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
@@ -199,35 +677,32 @@ pub struct UserData {
     pub about: String,
     #[serde(default)]
     pub submitted: Vec<i64>,
+    /// the stories, jobs and polls among the fetched submissions
     #[serde(default)]
     pub stories: Vec<StoryItem>,
+    /// the comments among the fetched submissions
+    #[serde(default)]
+    pub comments: Vec<Comment>,
+    /// the submissions which could not be fetched
+    #[serde(default)]
+    pub failed: Vec<FailedFetch>,
 }
 
 impl UserData {
-    /// attempt to extract user id from url (supports both hash and path routing)
-    pub fn id_from_url(url: &str) -> Option<String> {
-        let target_url = url.strip_prefix("#").unwrap_or(url);
-        if target_url.starts_with("user/") || url.starts_with("/user") {
-            let splinters = if target_url.starts_with("user/") {
-                target_url.split("/").collect::<Vec<_>>()
-            } else {
-                url.split("/").collect::<Vec<_>>()
-            };
-            if splinters.len() >= 2 {
-                if target_url.starts_with("user/") {
-                    Some(splinters[1].to_string())
-                } else {
-                    Some(splinters[2].to_string())
-                }
-            } else {
-                None
+    /// add the submissions which were fetched again, keeping the order they were submitted in
+    pub fn merge_submissions(&mut self, stories: Vec<StoryItem>, comments: Vec<Comment>) {
+        for story in stories {
+            if !self.stories.iter().any(|loaded| loaded.id == story.id) {
+                self.stories.push(story);
             }
-        } else {
-            None
         }
-    }
-
-    pub fn to_url(username: &str) -> String {
-        format!("#user/{}", username)
+        merge_replies(&self.submitted, &mut self.comments, comments);
+        let submitted = &self.submitted;
+        self.stories.sort_by_key(|story| {
+            submitted
+                .iter()
+                .position(|id| *id == story.id)
+                .unwrap_or(submitted.len())
+        });
     }
 }
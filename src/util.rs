@@ -28,7 +28,7 @@ fn linkify_text(text: &str) -> String {
             
             // Skip if this URL is already inside an <a> tag
             let before_url = &result[..actual_start];
-            if before_url.rfind("<a ").map_or(false, |a_pos| {
+            if before_url.rfind("<a ").is_some_and(|a_pos| {
                 before_url[a_pos..].find("</a>").is_none()
             }) {
                 search_pos = actual_start + protocol.len();
@@ -201,14 +201,14 @@ fn parse_simple_html<MSG>(html: &str) -> Vec<Node<MSG>> {
                             // Simple href extraction - find href="..." or href='...'
                             if let Some(href_start) = tag_content.find("href=\"") {
                                 let href_content_start = href_start + 6; // Skip 'href="'
-                                if let Some(href_end) = tag_content[href_content_start..].find('"') {
-                                    Some(tag_content[href_content_start..href_content_start + href_end].to_string())
-                                } else { None }
+                                tag_content[href_content_start..].find('"').map(|href_end| {
+                                    tag_content[href_content_start..href_content_start + href_end].to_string()
+                                })
                             } else if let Some(href_start) = tag_content.find("href='") {
                                 let href_content_start = href_start + 6; // Skip "href='"
-                                if let Some(href_end) = tag_content[href_content_start..].find('\'') {
-                                    Some(tag_content[href_content_start..href_content_start + href_end].to_string())
-                                } else { None }
+                                tag_content[href_content_start..].find('\'').map(|href_end| {
+                                    tag_content[href_content_start..href_content_start + href_end].to_string()
+                                })
                             } else { None }
                         } else { None };
                         
@@ -250,6 +250,41 @@ fn parse_simple_html<MSG>(html: &str) -> Vec<Node<MSG>> {
     nodes
 }
 
+/// Parse the json content of the `<meta>` tag named `name`, `None` if there is
+/// no such tag or if its content is not valid, which is logged
+#[cfg(feature = "wasm")]
+pub(crate) fn meta_json<T: serde::de::DeserializeOwned>(name: &str) -> Option<T> {
+    let selector = format!("meta[name={}]", name);
+    let meta = sauron::document().query_selector(&selector).ok()??;
+    let content = meta.get_attribute("content")?;
+    match serde_json::from_str(&content) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            log::warn!("invalid {} meta tag: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Return a duration of `ms` milliseconds in words, in whole seconds rounded up
+/// unless it is shorter than a second
+pub fn duration(ms: u64) -> String {
+    if ms < 1000 {
+        if ms == 1 {
+            format!("{} millisecond", ms)
+        } else {
+            format!("{} milliseconds", ms)
+        }
+    } else {
+        let seconds = ms.div_ceil(1000);
+        if seconds == 1 {
+            format!("{} second", seconds)
+        } else {
+            format!("{} seconds", seconds)
+        }
+    }
+}
+
 /// Return the time ago for a date
 pub fn time_ago(date: DateTime<Utc>) -> String {
     let now = Utc::now();
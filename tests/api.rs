@@ -0,0 +1,216 @@
+//! The api layer against canned responses, without going through the network.
+use hackernews_sauron::{
+    api::{
+        self,
+        ApiConfig,
+        FixtureTransport,
+        HnClient,
+        Resource,
+        ServerError,
+    },
+    types::StorySorting,
+};
+use serde_json::json;
+
+const TIME: i64 = 1_175_714_200;
+
+fn story(id: i64, kids: &[i64]) -> serde_json::Value {
+    json!({
+        "id": id,
+        "type": "story",
+        "by": "pg",
+        "title": format!("story {}", id),
+        "url": format!("https://example.com/{}", id),
+        "score": 10 * id,
+        "descendants": kids.len(),
+        "time": TIME,
+        "kids": kids,
+    })
+}
+
+fn comment(id: i64, parent: i64, kids: &[i64]) -> serde_json::Value {
+    json!({
+        "id": id,
+        "type": "comment",
+        "by": "norvig",
+        "parent": parent,
+        "text": format!("comment {}", id),
+        "time": TIME,
+        "kids": kids,
+    })
+}
+
+/// a client serving `items` and `users`, any other url is `null`
+fn client(
+    lists: &[(StorySorting, Vec<i64>)],
+    items: &[serde_json::Value],
+    users: &[serde_json::Value],
+) -> HnClient {
+    let config = ApiConfig::default();
+    let transport = FixtureTransport::new();
+    for (sorting, story_ids) in lists {
+        transport
+            .insert_json(config.stories_url(*sorting), story_ids)
+            .unwrap();
+    }
+    for item in items {
+        let id = item["id"].as_i64().unwrap();
+        transport.insert_json(config.item_url(id), item).unwrap();
+    }
+    for user in users {
+        let id = user["id"].as_str().unwrap();
+        transport.insert_json(config.user_url(id), user).unwrap();
+    }
+    HnClient::new(transport).with_config(config)
+}
+
+#[tokio::test]
+async fn fetches_a_page_of_stories_in_the_order_of_the_listing() {
+    let client = client(
+        &[(StorySorting::Ask, vec![1003, 1001, 1002, 1004])],
+        &[story(1001, &[]), story(1002, &[]), story(1003, &[])],
+        &[],
+    );
+    let page = api::get_stories_page(&client, StorySorting::Ask, 1, 3)
+        .await
+        .unwrap();
+    let ids = page.stories.iter().map(|story| story.id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![1003, 1001, 1002]);
+    assert_eq!(page.stories[0].title, "story 1003");
+    assert!(page.failed.is_empty());
+    assert!(page.has_more());
+
+    // 1004 is `null`, it is reported rather than failing the whole page
+    let next = api::get_stories_page(&client, StorySorting::Ask, 2, 3)
+        .await
+        .unwrap();
+    assert!(next.stories.is_empty());
+    assert_eq!(next.failed.len(), 1);
+    assert_eq!(next.failed[0].id, 1004);
+    assert!(matches!(next.failed[0].error, ServerError::NotFound { .. }));
+}
+
+#[tokio::test]
+async fn a_page_past_the_end_of_the_listing_is_empty() {
+    let client = client(&[], &[], &[]);
+    let page = api::get_stories_page_with_ids(
+        &client,
+        StorySorting::Top,
+        vec![1001, 1002],
+        usize::MAX,
+        30,
+    )
+    .await
+    .unwrap();
+    assert!(page.stories.is_empty());
+    assert!(page.failed.is_empty());
+    assert!(!page.has_more());
+}
+
+#[tokio::test]
+async fn fetches_a_story_with_its_comment_tree() {
+    let client = client(
+        &[],
+        &[
+            story(2001, &[2002, 2003, 2009]),
+            comment(2002, 2001, &[2004]),
+            comment(2003, 2001, &[]),
+            comment(2004, 2002, &[]),
+        ],
+        &[],
+    );
+    let story = api::get_story(&client, 2001).await.unwrap();
+    assert_eq!(story.title, "story 2001");
+    let ids = story.comments.iter().map(|c| c.id).collect::<Vec<_>>();
+    assert_eq!(ids, vec![2002, 2003]);
+    assert_eq!(story.comments[0].sub_comments[0].id, 2004);
+    assert_eq!(story.comments[0].sub_comments[0].text, "comment 2004");
+    // 2009 is `null`
+    assert_eq!(story.failed.len(), 1);
+    assert_eq!(story.failed[0].id, 2009);
+}
+
+#[tokio::test]
+async fn a_story_which_is_null_is_not_found() {
+    let client = client(&[], &[], &[]);
+    assert_eq!(
+        api::get_story(&client, 3001).await,
+        Err(ServerError::NotFound {
+            kind: Resource::Item,
+            id: String::from("3001"),
+        })
+    );
+}
+
+#[tokio::test]
+async fn fetches_a_user_with_their_submissions() {
+    let client = client(
+        &[],
+        &[story(4001, &[]), comment(4002, 4001, &[])],
+        &[json!({
+            "id": "fixture_user",
+            "karma": 155_111,
+            "about": "Bug fixer.",
+            "created": TIME,
+            "submitted": [4002, 4001],
+        })],
+    );
+    let user = api::get_user_page(&client, "fixture_user").await.unwrap();
+    assert_eq!(user.karma, 155_111);
+    assert_eq!(user.stories.len(), 1);
+    assert_eq!(user.stories[0].id, 4001);
+    assert_eq!(user.comments.len(), 1);
+    assert_eq!(user.comments[0].id, 4002);
+    assert!(user.failed.is_empty());
+}
+
+#[tokio::test]
+async fn a_user_who_is_null_is_not_found() {
+    let client = client(&[], &[], &[]);
+    assert_eq!(
+        api::get_user_page(&client, "nobody_at_all").await,
+        Err(ServerError::NotFound {
+            kind: Resource::User,
+            id: String::from("nobody_at_all"),
+        })
+    );
+}
+
+#[tokio::test]
+async fn reports_the_replies_which_failed_at_any_level() {
+    let client = client(
+        &[],
+        &[
+            story(5001, &[5002]),
+            comment(5002, 5001, &[5003, 5009]),
+            comment(5003, 5002, &[]),
+        ],
+        &[],
+    );
+    let story = api::get_story(&client, 5001).await.unwrap();
+    let replies = story.comments[0].sub_comments.iter().map(|c| c.id).collect::<Vec<_>>();
+    assert_eq!(replies, vec![5003]);
+    // 5009 is `null`, a reply to a comment rather than to the story
+    assert_eq!(story.failed.len(), 1);
+    assert_eq!(story.failed[0].id, 5009);
+    assert_eq!(story.parent_of(5009), Some(5002));
+}
+
+#[tokio::test]
+async fn clients_of_different_apis_do_not_share_their_caches() {
+    let mut renamed = story(7001, &[]);
+    renamed["title"] = json!("renamed story 7001");
+    let first = client(&[(StorySorting::Top, vec![7001])], &[story(7001, &[])], &[]);
+    let other = client(&[(StorySorting::Top, vec![7001])], &[renamed], &[]);
+
+    let page = api::get_stories_page(&first, StorySorting::Top, 1, 30)
+        .await
+        .unwrap();
+    assert_eq!(page.stories[0].title, "story 7001");
+    let page = api::get_stories_page(&other, StorySorting::Top, 1, 30)
+        .await
+        .unwrap();
+    assert_eq!(page.stories[0].title, "renamed story 7001");
+    // the clones of a client share its caches
+    assert!(api::get_cached_stories_page(&first.clone(), StorySorting::Top, 1, 30).is_some());
+}
@@ -0,0 +1,119 @@
+//! The update loop of the app, fed with the responses of a client reading canned responses.
+//!
+//! The commands returned by the update can only be run by the sauron runtime, so the
+//! requests they would do are done with the client here and their messages dispatched back.
+#![cfg(feature = "wasm")]
+use hackernews_sauron::{
+    api::{
+        self,
+        ApiConfig,
+        FixtureTransport,
+        HnClient,
+    },
+    sauron::Application,
+    types::StoryPageData,
+    App,
+    Content,
+    FetchStatus,
+    Msg,
+};
+use serde_json::json;
+
+fn story(id: i64, kids: &[i64]) -> serde_json::Value {
+    json!({
+        "id": id,
+        "type": "story",
+        "by": "pg",
+        "title": format!("story {}", id),
+        "descendants": kids.len(),
+        "time": 1_175_714_200,
+        "kids": kids,
+    })
+}
+
+fn comment(id: i64, parent: i64, kids: &[i64]) -> serde_json::Value {
+    json!({
+        "id": id,
+        "type": "comment",
+        "by": "norvig",
+        "parent": parent,
+        "text": format!("comment {}", id),
+        "time": 1_175_714_200,
+        "kids": kids,
+    })
+}
+
+/// a client serving `items`, any other url is `null`
+fn client(items: &[serde_json::Value]) -> HnClient {
+    let config = ApiConfig::default();
+    let transport = FixtureTransport::new();
+    for item in items {
+        let id = item["id"].as_i64().unwrap();
+        transport.insert_json(config.item_url(id), item).unwrap();
+    }
+    HnClient::new(transport).with_config(config)
+}
+
+/// the app displaying the story of `story_id` as fetched by `client`
+async fn app_with_story(client: &HnClient, story_id: i64) -> App {
+    let story = api::get_story(client, story_id).await.unwrap();
+    let mut app = App::with_client(client.clone());
+    app.content = FetchStatus::Complete(Content::from(story));
+    app
+}
+
+fn story_page(app: &App) -> &StoryPageData {
+    match &app.content {
+        FetchStatus::Complete(Content::StoryPage(story_page)) => story_page,
+        content => panic!("expecting a story page, got {:?}", content),
+    }
+}
+
+#[tokio::test]
+async fn the_replies_which_failed_are_put_under_their_parent_once_retried() {
+    let story = story(8001, &[8002]);
+    let items = [story.clone(), comment(8002, 8001, &[8003])];
+    let broken = client(&items);
+    let mut app = app_with_story(&broken, 8001).await;
+    assert_eq!(story_page(&app).failed.len(), 1);
+
+    // the reply can be fetched now
+    let fixed = client(&[story, comment(8002, 8001, &[8003]), comment(8003, 8002, &[])]);
+    let retried = api::get_items(&fixed, &[8003]).await;
+    let _ = app.update(Msg::ReceivedRetried(retried));
+
+    let story_page = story_page(&app);
+    assert!(story_page.failed.is_empty());
+    assert_eq!(story_page.comments.len(), 1);
+    assert_eq!(story_page.comments[0].sub_comments[0].id, 8003);
+}
+
+#[tokio::test]
+async fn loads_the_replies_left_out_of_the_comment_tree() {
+    let client = client(&[
+        story(8101, &[8102]),
+        comment(8102, 8101, &[8103, 8104, 8105, 8106]),
+        comment(8103, 8102, &[]),
+        comment(8104, 8102, &[]),
+        comment(8105, 8102, &[]),
+        comment(8106, 8102, &[]),
+    ]);
+    let mut app = app_with_story(&client, 8101).await;
+    let loaded = story_page(&app).comments[0].sub_comments.len();
+    assert!(loaded < 4);
+
+    let _ = app.update(Msg::LoadMoreReplies(8102));
+    let unloaded = match &app.content {
+        FetchStatus::Complete(content) => content.unloaded_replies(8102),
+        _ => unreachable!(),
+    };
+    let replies = api::get_comments(&client, &unloaded).await;
+    let _ = app.update(Msg::ReceivedReplies(8102, replies.items));
+
+    let replies = story_page(&app).comments[0]
+        .sub_comments
+        .iter()
+        .map(|reply| reply.id)
+        .collect::<Vec<_>>();
+    assert_eq!(replies, vec![8103, 8104, 8105, 8106]);
+}
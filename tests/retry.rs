@@ -0,0 +1,162 @@
+//! Retrying the requests and timing them out, through a transport which fails
+//! as many times as it is told to before responding.
+use hackernews_sauron::api::{
+    self,
+    ApiConfig,
+    HnClient,
+    HnTransport,
+    Resource,
+    RetryPolicy,
+    ServerError,
+    TransportFuture,
+};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        Mutex,
+    },
+};
+
+const URL: &str = "https://example.com/retried";
+
+/// What the transport does with a request
+#[derive(Clone)]
+enum Outcome {
+    Fail(ServerError),
+    /// never respond
+    Hang,
+}
+
+/// A transport going through its `script` one request at a time, and
+/// responding with the url once the script is done
+#[derive(Clone, Default)]
+struct ScriptedTransport {
+    script: Arc<Mutex<VecDeque<Outcome>>>,
+    calls: Arc<AtomicUsize>,
+}
+
+impl ScriptedTransport {
+    fn new(script: impl IntoIterator<Item = Outcome>) -> Self {
+        Self {
+            script: Arc::new(Mutex::new(script.into_iter().collect())),
+            calls: Arc::default(),
+        }
+    }
+
+    fn calls(&self) -> usize {
+        self.calls.load(Ordering::SeqCst)
+    }
+}
+
+impl HnTransport for ScriptedTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        let outcome = self.script.lock().unwrap().pop_front();
+        Box::pin(async move {
+            match outcome {
+                Some(Outcome::Fail(error)) => Err(error),
+                Some(Outcome::Hang) => futures::future::pending().await,
+                None => Ok(serde_json::to_string(url)?),
+            }
+        })
+    }
+}
+
+/// a client trying every request `max_attempts` times, with barely any backoff
+fn client(transport: &ScriptedTransport, max_attempts: u32) -> HnClient {
+    let config = ApiConfig {
+        timeout_ms: Some(50),
+        retry: RetryPolicy {
+            max_attempts,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 2,
+        },
+        ..ApiConfig::default()
+    };
+    HnClient::new(transport.clone()).with_config(config)
+}
+
+async fn get(client: &HnClient) -> Result<String, ServerError> {
+    api::make_json_get_request::<String>(client, URL).await
+}
+
+#[tokio::test]
+async fn server_errors_and_timeouts_are_retried() {
+    let transport = ScriptedTransport::new(vec![
+        Outcome::Fail(ServerError::HttpStatus(503)),
+        Outcome::Hang,
+    ]);
+    let client = client(&transport, 3);
+    assert_eq!(get(&client).await, Ok(URL.to_string()));
+    assert_eq!(transport.calls(), 3);
+}
+
+#[tokio::test]
+async fn missing_items_are_not_retried() {
+    let not_found = ServerError::NotFound {
+        kind: Resource::Item,
+        id: String::from("8863"),
+    };
+    let not_in_snapshot = ServerError::NotInSnapshot(URL.to_string());
+    for error in [not_found, not_in_snapshot] {
+        let transport = ScriptedTransport::new(vec![Outcome::Fail(error.clone())]);
+        let client = client(&transport, 3);
+        assert_eq!(get(&client).await, Err(error));
+        assert_eq!(transport.calls(), 1);
+    }
+}
+
+#[tokio::test]
+async fn gives_up_after_max_attempts() {
+    let transport =
+        ScriptedTransport::new(vec![Outcome::Fail(ServerError::HttpStatus(500)); 10]);
+    let client = client(&transport, 4);
+    assert_eq!(get(&client).await, Err(ServerError::HttpStatus(500)));
+    assert_eq!(transport.calls(), 4);
+}
+
+#[tokio::test]
+async fn a_request_which_never_responds_times_out() {
+    let transport = ScriptedTransport::new(vec![Outcome::Hang]);
+    let client = client(&transport, 1);
+    assert_eq!(get(&client).await, Err(ServerError::Timeout(50)));
+    assert_eq!(transport.calls(), 1);
+}
+
+#[test]
+fn only_the_transient_statuses_are_retryable() {
+    for status in [500, 502, 503, 429, 408].iter() {
+        assert!(ServerError::HttpStatus(*status).is_retryable(), "{}", status);
+    }
+    for status in [400, 401, 403, 404].iter() {
+        assert!(!ServerError::HttpStatus(*status).is_retryable(), "{}", status);
+    }
+    assert!(ServerError::Timeout(50).is_retryable());
+    assert!(!ServerError::Offline.is_retryable());
+}
+
+#[test]
+fn the_backoff_doubles_up_to_its_max_with_half_of_it_random() {
+    let policy = RetryPolicy {
+        max_attempts: 20,
+        initial_backoff_ms: 100,
+        max_backoff_ms: 1_000,
+    };
+    for attempt in 1..20 {
+        let backoff = (100u64 << (attempt - 1).min(16)).min(1_000);
+        for _ in 0..20 {
+            let delay = policy.backoff_ms(attempt);
+            assert!(
+                (backoff - backoff / 2..=backoff).contains(&delay),
+                "a delay of {}ms after {} attempts",
+                delay,
+                attempt
+            );
+        }
+    }
+}
@@ -0,0 +1,243 @@
+use hackernews_sauron::{
+    api::SearchQuery,
+    route::{
+        Route,
+        RouteError,
+        MAX_PAGE,
+        Router,
+        RouterMode,
+        UnknownRoute,
+    },
+    types::StorySorting,
+};
+use proptest::prelude::*;
+
+fn sorting() -> impl Strategy<Value = StorySorting> {
+    prop::sample::select(StorySorting::all())
+}
+
+fn search() -> impl Strategy<Value = SearchQuery> {
+    (
+        any::<String>(),
+        proptest::option::of(any::<String>()),
+        proptest::option::of(any::<String>()),
+        any::<usize>(),
+        proptest::option::of(any::<usize>()),
+    )
+        .prop_map(|(query, tags, numeric_filters, page, hits_per_page)| {
+            SearchQuery {
+                query,
+                tags,
+                numeric_filters,
+                page,
+                hits_per_page,
+            }
+        })
+}
+
+fn route() -> impl Strategy<Value = Route> {
+    prop_oneof![
+        Just(Route::Home),
+        (sorting(), 1..=MAX_PAGE)
+            .prop_map(|(sorting, page)| Route::Stories { sorting, page }),
+        any::<i64>().prop_map(Route::Story),
+        any::<i64>().prop_map(Route::Comment),
+        "[a-zA-Z0-9_-]{1,15}".prop_map(Route::User),
+        search().prop_map(Route::Search),
+    ]
+}
+
+fn router() -> impl Strategy<Value = Router> {
+    (
+        prop_oneof![Just(RouterMode::Hash), Just(RouterMode::History)],
+        prop_oneof![Just(""), Just("/"), Just("/hackernews-sauron/"), Just("/a/b")],
+    )
+        .prop_map(|(mode, base_path)| Router::new(mode, base_path))
+}
+
+proptest! {
+    #[test]
+    fn every_route_round_trips(route in route()) {
+        prop_assert_eq!(Route::parse(&route.to_url()), Ok(route));
+    }
+
+    #[test]
+    fn the_path_of_a_route_is_the_same_route(route in route()) {
+        let path = format!("/{}", route.to_url().trim_start_matches('#'));
+        prop_assert_eq!(Route::parse(&path), Ok(route));
+    }
+
+    #[test]
+    fn only_the_hash_of_a_url_is_routed(route in route()) {
+        let url = format!("https://news.example.com/item/1{}", route.to_url());
+        prop_assert_eq!(Route::parse(&url), Ok(route));
+    }
+
+    #[test]
+    fn the_href_of_a_route_is_routed_back(router in router(), route in route()) {
+        prop_assert_eq!(router.route(&router.href(&route)), Ok(route));
+    }
+
+    #[test]
+    fn parsing_never_panics(url in any::<String>()) {
+        let _ = Route::parse(&url);
+    }
+
+    #[test]
+    fn a_route_is_the_nearest_to_itself(route in route()) {
+        let nearest = Route::nearest(&route.to_url());
+        prop_assert_eq!(nearest, Some(route).filter(|route| *route != Route::Home));
+    }
+
+    #[test]
+    fn the_suggestions_are_routes(url in any::<String>()) {
+        if let Some(suggestion) = Route::nearest(&url) {
+            prop_assert_eq!(Route::parse(&suggestion.to_url()), Ok(suggestion));
+        }
+    }
+}
+
+#[test]
+fn parses_the_paths_of_the_server() {
+    assert_eq!(Route::parse("/"), Ok(Route::Home));
+    assert_eq!(Route::parse("/item/8863"), Ok(Route::Story(8863)));
+    assert_eq!(Route::parse("/comment/2921983"), Ok(Route::Comment(2921983)));
+    assert_eq!(Route::parse("/user/pg"), Ok(Route::User(String::from("pg"))));
+    assert_eq!(
+        Route::parse("/new/page/2"),
+        Ok(Route::Stories {
+            sorting: StorySorting::New,
+            page: 2
+        })
+    );
+}
+
+#[test]
+fn ignores_trailing_slashes_and_query_strings() {
+    assert_eq!(Route::parse("#item/8863/"), Ok(Route::Story(8863)));
+    assert_eq!(Route::parse("/item/8863?utm_source=rss"), Ok(Route::Story(8863)));
+    assert_eq!(
+        Route::parse("#top/?ref=home"),
+        Ok(Route::Stories {
+            sorting: StorySorting::Top,
+            page: 1
+        })
+    );
+    assert_eq!(Route::parse(""), Ok(Route::Home));
+    assert_eq!(Route::parse("#"), Ok(Route::Home));
+}
+
+#[test]
+fn parses_the_query_of_a_search() {
+    let route = Route::parse("#search?q=rust%20wasm&page=2").unwrap();
+    let mut search = SearchQuery::new("rust wasm");
+    search.page = 2;
+    assert_eq!(route, Route::Search(search));
+}
+
+#[test]
+fn rejects_the_urls_which_are_not_routes() {
+    assert_eq!(
+        Route::parse("#item/abc"),
+        Err(RouteError::InvalidId(String::from("abc")))
+    );
+    assert_eq!(
+        Route::parse("#top/page/0"),
+        Err(RouteError::InvalidPage(String::from("0")))
+    );
+    assert_eq!(
+        Route::parse("#top/page/9999999999999999999"),
+        Err(RouteError::InvalidPage(String::from("9999999999999999999")))
+    );
+    assert_eq!(
+        Route::parse("#flagged"),
+        Err(RouteError::Unknown(String::from("#flagged")))
+    );
+    assert_eq!(
+        Route::parse("#item/1/2"),
+        Err(RouteError::Unknown(String::from("#item/1/2")))
+    );
+}
+
+#[test]
+fn puts_the_routes_after_the_base_path() {
+    let hash = Router::new(RouterMode::Hash, "/hackernews-sauron/");
+    assert_eq!(hash.href(&Route::Story(8863)), "/hackernews-sauron/#item/8863");
+    assert_eq!(Router::default().href(&Route::Story(8863)), "#item/8863");
+
+    let history = Router::new(RouterMode::History, "/hackernews-sauron");
+    assert_eq!(history.href(&Route::Home), "/hackernews-sauron/");
+    assert_eq!(history.href(&Route::Story(8863)), "/hackernews-sauron/item/8863");
+    assert_eq!(Router::new(RouterMode::History, "").href(&Route::Home), "/");
+}
+
+#[test]
+fn routes_the_page_the_app_is_served_from() {
+    let hash = Router::new(RouterMode::Hash, "/hackernews-sauron");
+    assert_eq!(hash.route("/hackernews-sauron/index.html"), Ok(Route::Home));
+    assert_eq!(hash.route("/somewhere/else#"), Ok(Route::Home));
+    assert_eq!(hash.route("/hackernews-sauron/item/8863"), Ok(Route::Story(8863)));
+
+    let history = Router::new(RouterMode::History, "/hackernews-sauron");
+    assert_eq!(history.route("/hackernews-sauron"), Ok(Route::Home));
+    assert_eq!(history.route("/hackernews-sauron/index.html"), Ok(Route::Home));
+    assert_eq!(history.route("/hackernews-sauron/user/pg#top"), Ok(Route::User(String::from("pg"))));
+    assert!(history.route("/hackernews-sauron/flagged").is_err());
+}
+
+#[test]
+fn rejects_the_malformed_ids() {
+    let router = Router::default();
+    for url in ["#item/abc", "#item/-", "#comment/12.5", "#item/99999999999999999999"] {
+        assert!(
+            matches!(router.route(url), Err(RouteError::InvalidId(_))),
+            "{} is routed",
+            url
+        );
+    }
+    assert_eq!(Route::nearest("#item/abc"), None);
+    assert_eq!(Route::nearest("#comment/12.5"), None);
+    assert_eq!(Route::nearest("#item/1e9"), None);
+    assert_eq!(Route::nearest("#top/page/1.5"), None);
+}
+
+#[test]
+fn suggests_the_nearest_route() {
+    assert_eq!(Route::nearest("#item/8863x"), Some(Route::Story(8863)));
+    assert_eq!(Route::nearest("#itme/8863/foo"), Some(Route::Story(8863)));
+    assert_eq!(Route::nearest("#Comment/2921983"), Some(Route::Comment(2921983)));
+    assert_eq!(Route::nearest("#usr/PG"), Some(Route::User(String::from("PG"))));
+    assert_eq!(
+        Route::nearest("#nwe"),
+        Some(Route::Stories {
+            sorting: StorySorting::New,
+            page: 1
+        })
+    );
+    assert_eq!(
+        Route::nearest("#best/page/0"),
+        Some(Route::Stories {
+            sorting: StorySorting::Best,
+            page: 1
+        })
+    );
+    assert_eq!(Route::nearest("#flagged"), None);
+    assert_eq!(Route::nearest("#flagged/page/2"), None);
+}
+
+#[test]
+fn suggests_the_nearest_route_after_the_base_path() {
+    let router = Router::new(RouterMode::History, "/hackernews-sauron");
+    assert_eq!(
+        router.unknown("/hackernews-sauron/itme/8863"),
+        UnknownRoute {
+            url: String::from("/hackernews-sauron/itme/8863"),
+            suggestion: Some(Route::Story(8863)),
+        }
+    );
+    let hash = Router::new(RouterMode::Hash, "/hackernews-sauron");
+    assert_eq!(
+        hash.unknown("/hackernews-sauron/#user/pg/submissions").suggestion,
+        Some(Route::User(String::from("pg")))
+    );
+}
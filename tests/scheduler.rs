@@ -0,0 +1,174 @@
+//! The scheduler of the client, through a transport which holds the requests
+//! until they are let through and counts them.
+use hackernews_sauron::api::{
+    self,
+    ApiConfig,
+    HnClient,
+    HnTransport,
+    TransportFuture,
+};
+use std::{
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::Semaphore;
+
+#[derive(Default)]
+struct Counters {
+    /// how many requests reached the transport
+    calls: AtomicUsize,
+    /// how many requests are in the transport right now
+    in_flight: AtomicUsize,
+    /// the most requests which were in the transport at once
+    max_in_flight: AtomicUsize,
+}
+
+/// A transport whose requests wait for a permit of `gate`, so none is done until they are let through
+#[derive(Clone)]
+struct GatedTransport {
+    counters: Arc<Counters>,
+    gate: Arc<Semaphore>,
+}
+
+impl GatedTransport {
+    fn new() -> Self {
+        Self {
+            counters: Arc::new(Counters::default()),
+            gate: Arc::new(Semaphore::new(0)),
+        }
+    }
+
+    /// let `count` more requests through
+    fn open(&self, count: usize) {
+        self.gate.add_permits(count);
+    }
+
+    fn calls(&self) -> usize {
+        self.counters.calls.load(Ordering::SeqCst)
+    }
+
+    fn in_flight(&self) -> usize {
+        self.counters.in_flight.load(Ordering::SeqCst)
+    }
+
+    fn max_in_flight(&self) -> usize {
+        self.counters.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+impl HnTransport for GatedTransport {
+    fn get<'a>(&'a self, url: &'a str) -> TransportFuture<'a> {
+        Box::pin(async move {
+            let counters = &self.counters;
+            counters.calls.fetch_add(1, Ordering::SeqCst);
+            let in_flight = counters.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            counters.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            self.gate.acquire().await.expect("the gate is never closed").forget();
+            counters.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(serde_json::to_string(url)?)
+        })
+    }
+}
+
+fn client(transport: &GatedTransport, max_in_flight: usize) -> HnClient {
+    let config = ApiConfig {
+        max_in_flight,
+        ..ApiConfig::default()
+    };
+    HnClient::new(transport.clone()).with_config(config)
+}
+
+/// spawn a request of `url`, which responds with the url itself
+fn spawn_get(
+    client: &HnClient,
+    url: &str,
+) -> tokio::task::JoinHandle<Result<String, api::ServerError>> {
+    let client = client.clone();
+    let url = url.to_string();
+    tokio::spawn(async move { api::make_json_get_request(&client, &url).await })
+}
+
+/// let the spawned requests run until `done` holds
+async fn run_until(done: impl Fn() -> bool) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !done() {
+            tokio::task::yield_now().await;
+        }
+    })
+    .await
+    .expect("the requests got stuck");
+}
+
+#[tokio::test]
+async fn no_more_than_max_in_flight_requests_are_done_at_once() {
+    let transport = GatedTransport::new();
+    let client = client(&transport, 3);
+    let requests = (0..10)
+        .map(|i| spawn_get(&client, &format!("https://example.com/{}", i)))
+        .collect::<Vec<_>>();
+    run_until(|| transport.in_flight() == 3).await;
+    // the others are waiting for a slot
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    assert_eq!(transport.calls(), 3);
+
+    transport.open(10);
+    for request in requests {
+        request.await.unwrap().unwrap();
+    }
+    assert_eq!(transport.calls(), 10);
+    assert_eq!(transport.max_in_flight(), 3);
+}
+
+#[tokio::test]
+async fn concurrent_requests_for_the_same_url_are_done_once() {
+    let transport = GatedTransport::new();
+    let client = client(&transport, 3);
+    let url = "https://example.com/shared";
+    let first = spawn_get(&client, url);
+    let second = spawn_get(&client, url);
+    run_until(|| transport.calls() == 1).await;
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+
+    transport.open(2);
+    assert_eq!(first.await.unwrap().unwrap(), url);
+    assert_eq!(second.await.unwrap().unwrap(), url);
+    assert_eq!(transport.calls(), 1);
+}
+
+#[tokio::test]
+async fn a_request_dropped_while_waiting_does_not_keep_its_slot() {
+    let transport = GatedTransport::new();
+    let client = client(&transport, 1);
+    let holding = spawn_get(&client, "https://example.com/holding");
+    run_until(|| transport.in_flight() == 1).await;
+
+    // waits for the only slot, and is dropped before getting it
+    let dropped = tokio::time::timeout(
+        Duration::from_millis(20),
+        api::make_json_get_request::<String>(&client, "https://example.com/dropped"),
+    )
+    .await;
+    assert!(dropped.is_err());
+
+    transport.open(1);
+    holding.await.unwrap().unwrap();
+    transport.open(1);
+    let next = tokio::time::timeout(
+        Duration::from_secs(5),
+        api::make_json_get_request::<String>(&client, "https://example.com/next"),
+    )
+    .await
+    .expect("the slot of the dropped request was not released");
+    assert_eq!(next.unwrap(), "https://example.com/next");
+    assert_eq!(transport.calls(), 2);
+}
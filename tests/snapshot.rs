@@ -0,0 +1,83 @@
+//! Reading the api from a snapshot directory, which must have a manifest of the current version.
+use hackernews_sauron::api::{
+    self,
+    ApiConfig,
+    HnClient,
+    Manifest,
+    ServerError,
+    SnapshotTransport,
+    MANIFEST_FILE,
+    SNAPSHOT_VERSION,
+};
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// a snapshot directory of its own for `name`, with item 6001 in it and `manifest` if any
+fn snapshot(name: &str, manifest: Option<u32>) -> PathBuf {
+    let dir = std::env::temp_dir()
+        .join(format!("hn-snapshot-{}-{}", name, std::process::id()));
+    fs::create_dir_all(dir.join("item")).unwrap();
+    fs::write(
+        dir.join("item/6001.json"),
+        r#"{"id": 6001, "type": "story", "by": "pg", "title": "story 6001", "time": 1175714200}"#,
+    )
+    .unwrap();
+    if let Some(version) = manifest {
+        let manifest = Manifest {
+            version,
+            created_at: chrono::Utc::now(),
+            source: ApiConfig::default().base_url,
+            stories: vec![],
+            items: 1,
+            users: 0,
+        };
+        fs::write(dir.join(MANIFEST_FILE), serde_json::to_string(&manifest).unwrap())
+            .unwrap();
+    }
+    dir
+}
+
+fn client(dir: &Path) -> HnClient {
+    let config = ApiConfig::default();
+    let transport =
+        SnapshotTransport::new(dir.to_string_lossy(), config.base_url.clone());
+    HnClient::new(transport).with_config(config)
+}
+
+#[tokio::test]
+async fn reads_the_items_of_the_snapshot() {
+    let client = client(&snapshot("valid", Some(SNAPSHOT_VERSION)));
+    let url = client.config().item_url(6001);
+    let item = api::make_json_get_request::<serde_json::Value>(&client, &url)
+        .await
+        .unwrap();
+    assert_eq!(item["title"], "story 6001");
+
+    // the items which are not exported are `null`, as in the api
+    let url = client.config().item_url(6002);
+    let item = api::make_json_get_request::<serde_json::Value>(&client, &url)
+        .await
+        .unwrap();
+    assert!(item.is_null());
+}
+
+#[tokio::test]
+async fn a_snapshot_without_a_manifest_is_not_read() {
+    let client = client(&snapshot("partial", None));
+    let url = client.config().item_url(6001);
+    let result = api::make_json_get_request::<serde_json::Value>(&client, &url).await;
+    assert!(matches!(result, Err(ServerError::InvalidSnapshot(_))));
+}
+
+#[tokio::test]
+async fn a_snapshot_of_another_version_is_not_read() {
+    let client = client(&snapshot("outdated", Some(SNAPSHOT_VERSION + 1)));
+    let url = client.config().item_url(6001);
+    let result = api::make_json_get_request::<serde_json::Value>(&client, &url).await;
+    assert!(matches!(result, Err(ServerError::InvalidSnapshot(_))));
+}
@@ -0,0 +1,11 @@
+use hackernews_sauron::util::duration;
+
+#[test]
+fn durations_are_in_milliseconds_below_a_second_and_rounded_up_seconds_above() {
+    assert_eq!(duration(1), "1 millisecond");
+    assert_eq!(duration(250), "250 milliseconds");
+    assert_eq!(duration(999), "999 milliseconds");
+    assert_eq!(duration(1000), "1 second");
+    assert_eq!(duration(1500), "2 seconds");
+    assert_eq!(duration(10_000), "10 seconds");
+}